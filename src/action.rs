@@ -1,25 +1,60 @@
-use std::path::PathBuf;
+use std::{
+  io::{self, Write},
+  path::PathBuf,
+};
+
+/// What separates the paths printed by `Action::PrintMany`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Separator {
+  #[default]
+  Newline,
+  Nul,
+}
+
+impl Separator {
+  fn as_bytes(&self) -> &[u8] {
+    match self {
+      Separator::Newline => b"\n",
+      Separator::Nul => b"\0",
+    }
+  }
+}
 
 pub enum Action {
   Change(PathBuf),
   Keep,
   Print(PathBuf),
+  PrintMany(Vec<PathBuf>, Separator),
 }
 
 impl Action {
-  pub fn execute(&self) -> PathBuf {
+  /// The paths to print are written to `out`
+  pub fn execute<W: Write>(&self, out: &mut W) -> PathBuf {
     let current = PathBuf::from(".");
     match self {
       Action::Change(cd_path) => cd_path.into(),
       Action::Keep => current,
       Action::Print(print_path) => {
-        println!("{}", print_path.display());
+        let _ = writeln!(out, "{}", print_path.display());
+        current
+      }
+      Action::PrintMany(paths, separator) => {
+        let _ = write_paths(out, paths, *separator);
         current
       }
     }
   }
 }
 
+/// Paths are written as they are, even if they are not UTF-8
+fn write_paths<W: Write>(w: &mut W, paths: &[PathBuf], separator: Separator) -> io::Result<()> {
+  for path in paths {
+    w.write_all(path.as_os_str().as_encoded_bytes())?;
+    w.write_all(separator.as_bytes())?;
+  }
+  w.flush()
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -28,11 +63,25 @@ mod tests {
   fn test_action_execute() {
     let current = PathBuf::from(".");
     let cd_path = PathBuf::from("/tmp");
+    let mut out = vec![];
     let action = Action::Change(cd_path.clone());
-    assert_eq!(action.execute(), cd_path);
+    assert_eq!(action.execute(&mut out), cd_path);
     let action = Action::Keep;
-    assert_eq!(action.execute(), current);
+    assert_eq!(action.execute(&mut out), current);
+    assert!(out.is_empty());
     let action = Action::Print(cd_path);
-    assert_eq!(action.execute(), current);
+    assert_eq!(action.execute(&mut out), current);
+    assert_eq!(out, b"/tmp\n");
+  }
+
+  #[test]
+  fn test_write_paths() {
+    let paths = [PathBuf::from("/tmp/a"), PathBuf::from("/tmp/b c")];
+    let mut out = vec![];
+    write_paths(&mut out, &paths, Separator::Newline).unwrap();
+    assert_eq!(out, b"/tmp/a\n/tmp/b c\n");
+    let mut out = vec![];
+    write_paths(&mut out, &paths, Separator::Nul).unwrap();
+    assert_eq!(out, b"/tmp/a\0/tmp/b c\0");
   }
 }
@@ -1,6 +1,10 @@
 use std::{
-  env, io, mem,
+  env,
+  fs::{File, OpenOptions},
+  io::{self, BufWriter, Write},
+  mem,
   path::{Path, PathBuf},
+  time::{Duration, Instant},
   vec,
 };
 
@@ -10,11 +14,33 @@ use ratatui::crossterm::{
   execute,
   terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
-use ratatui::{Terminal, backend::CrosstermBackend};
+use ratatui::{
+  Terminal,
+  backend::{Backend, CrosstermBackend},
+};
+
+use super::fileop;
+use super::rename::{self, Rename};
+use super::status::describe;
+use super::{
+  Clipboard, Column, Grep, GrepMatch, Highlighter, Item, ItemInfo, ItemPath, Loader, Picker, PickerEntry, PickerKind,
+  Prompt, PromptKind, Query, ReadOptions, Search, State, StatefulList, Status, Walk,
+};
+use crate::bookmark::Bookmarks;
+use crate::error::{AppError, FileSystemError, PrintError, UiError};
+use crate::history::History;
+use crate::journal::Journal;
+use crate::{
+  Config,
+  action::{Action, Separator},
+};
 
-use super::{Item, ItemInfo, Search, State, StatefulList};
-use crate::error::{AppError, FileSystemError, UiError};
-use crate::{Config, action::Action};
+/// Results of the recursive search or grep shown at most
+const MAX_WALK_RESULTS: usize = 1000;
+/// A column waits this long for the background read before showing it as loading
+const LOAD_WAIT: Duration = Duration::from_millis(30);
+/// grep starts over once the search text has not changed for this long
+const GREP_DEBOUNCE: Duration = Duration::from_millis(150);
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AppMode {
@@ -22,6 +48,16 @@ pub enum AppMode {
   Search,
 }
 
+/// What the working block selects once it is read in the background,
+/// instead of the item at the path selected before
+#[derive(Debug)]
+enum Selection {
+  Index(usize),
+  Path(PathBuf),
+  /// The line in the contents of the file, which follows the preview if it is beyond it
+  Line(GrepMatch),
+}
+
 #[derive(Debug)]
 pub struct App {
   pub mode: AppMode,
@@ -32,10 +68,38 @@ pub struct App {
   pub wd: PathBuf,
   grandparent_path: PathBuf,
   pub search: Search,
+  pub picker: Option<Picker>,
+  /// Recursive search below `wd`
+  pub walk: Option<Walk>,
+  /// Search inside the files below `wd`
+  pub grep: Option<Grep>,
+  /// When the search text last changed from the pattern of `grep`, which starts over after `GREP_DEBOUNCE`
+  pub grep_restart: Option<Instant>,
+  /// Paths marked across directories, in the order they are marked
+  pub marks: Vec<PathBuf>,
+  /// Opened with `--pick`, `Enter` prints the marked paths separated by it
+  pub pick: Option<Separator>,
+  /// stdout is a pipeline, for `--pick` or `--stdout`, so the TUI and the programs run from it use the terminal instead
+  pub piped: bool,
+  pub clipboard: Option<Clipboard>,
+  /// `None` if there is no home directory to keep the journal in
+  journal_path: Option<PathBuf>,
+  /// Journal ids of the renames and moves of this session, grouped by operation.
+  /// The last group is undone first.
+  undo: Vec<Vec<u64>>,
+  /// Input line of a file operation, which takes the keys while it is open
+  pub prompt: Option<Prompt>,
+  pub status: Option<Status>,
+  /// Highlighted lines of the file contents shown
+  pub highlighter: Highlighter,
+  /// Reads the columns in the background, or in place if `None`
+  pub loader: Option<Loader>,
+  /// Selected in the working block once it is read
+  selection: Option<Selection>,
+  pub read_options: ReadOptions,
   pub config: Config,
 }
 
-const JUMP: usize = 4;
 impl App {
   fn generate_index<P: AsRef<Path>>(items: &[ItemInfo], path: P) -> usize {
     let generate_item = items.iter().enumerate().find(|(_, item)| item.get_path().is_some_and(|p| p == path.as_ref()));
@@ -87,7 +151,7 @@ impl App {
   /// If the working block is "content" `true`
   fn is_contents_in_working_block(&self) -> bool {
     let i = self.parent_items.selected();
-    self.get_parent_items()[i].is_file()
+    self.parent_items.items.get(i).is_some_and(|item| item.is_file())
   }
   fn is_child_empty(&self) -> bool {
     self.child_items.items.is_empty()
@@ -98,13 +162,59 @@ impl App {
       AppMode::Search => self.search.list.is_empty(),
     }
   }
+  /// Rebuild all lists around `path`, as on startup
+  pub fn jump<P: AsRef<Path>>(&mut self, path: P) -> anyhow::Result<()> {
+    self.jump_selecting(path.as_ref().into(), None)
+  }
+  /// `jump`, selecting `selection` in the working block once it is read.
+  /// The working block is the contents of `wd` if it is a file.
+  fn jump_selecting(&mut self, wd: PathBuf, selection: Option<Selection>) -> anyhow::Result<()> {
+    if !wd.exists() {
+      return Err(FileSystemError::InvalidPath(wd.to_string_lossy().into()).into());
+    }
+    let parent_path = Self::generate_parent_path(&wd);
+    self.grandparent_path = Self::generate_parent_path(&parent_path);
+    self.wd = wd;
+    self.search = Search::new();
+    self.picker = None;
+    self.walk = None;
+    self.grep = None;
+    self.mode = AppMode::Normal;
+    self.selection = selection;
+    self.cancel_loading(Column::Child);
+    self.grandparent_items = Self::loading(Some(0));
+    self.parent_items = Self::loading(Some(0));
+    self.items = Self::loading(Some(0));
+    self.child_items = Self::loading(None);
+
+    self.load(Column::Grandparent, Self::column_item(&self.grandparent_path))?;
+    self.load(Column::Parent, Self::column_item(parent_path))?;
+    self.load(Column::Items, Self::column_item(&self.wd))?;
+    self.finish_loading(Some(LOAD_WAIT))
+  }
   pub fn judge_mode(&self) -> AppMode {
-    if self.search.text.is_empty() { AppMode::Normal } else { AppMode::Search }
+    if self.search.text.is_empty() && !self.is_subtree_search() { AppMode::Normal } else { AppMode::Search }
+  }
+  /// If the recursive search or grep is on `true`
+  pub fn is_subtree_search(&self) -> bool {
+    self.walk.is_some() || self.grep.is_some()
   }
-  pub fn make_items<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<ItemInfo>> {
-    Ok(if path.as_ref().to_string_lossy().is_empty() { vec![ItemInfo::default()] } else { super::read_items(path)? })
+  /// If the recursive search or grep is still finding results `true`
+  pub fn is_subtree_searching(&self) -> bool {
+    self.walk.as_ref().is_some_and(|walk| !walk.done)
+      || self.grep.as_ref().is_some_and(|grep| !grep.walk.done || self.grep_restart.is_some())
+  }
+  pub fn make_items<P: AsRef<Path>>(path: P, options: ReadOptions) -> anyhow::Result<Vec<ItemInfo>> {
+    Ok(if path.as_ref().to_string_lossy().is_empty() {
+      vec![ItemInfo::default()]
+    } else {
+      super::read_items(path, options)?
+    })
   }
   pub fn move_child(&mut self) -> anyhow::Result<()> {
+    if self.is_subtree_search() {
+      return self.jump_to_result();
+    }
     if self.is_empty_in_working_block() || self.is_child_empty() {
       return Ok(());
     }
@@ -124,11 +234,7 @@ impl App {
     let selected_ci = self.get_child_index();
 
     // The index of `items` is set to the index of `child_items` if it is selected. If not, it is set to `0`.
-    let (new_child_items, new_i) = if let Some(items) = self.get_child_items().get(selected_ci) {
-      (items.generate_child_items()?, self.get_child_index())
-    } else {
-      (self.get_child_items().first().unwrap_or(&ItemInfo::default()).generate_child_items()?, 0)
-    };
+    let new_i = if self.get_child_items().get(selected_ci).is_some() { selected_ci } else { 0 };
 
     let new_pi = match self.judge_mode() {
       AppMode::Normal => Some(self.get_current_index()),
@@ -142,15 +248,15 @@ impl App {
     self.search = Search::new();
     self.grandparent_items = mem::replace(
       &mut self.parent_items,
-      mem::replace(
-        &mut self.items,
-        mem::replace(&mut self.child_items, StatefulList::with_items_option(new_child_items, None)),
-      ),
+      mem::replace(&mut self.items, mem::replace(&mut self.child_items, StatefulList::with_items_option(vec![], None))),
     );
     self.items.state.select(Some(new_i));
     self.parent_items.state.select(new_pi);
 
-    Ok(())
+    // The child column may still be read, and becomes the working block once it is
+    self.shift_loading(Column::after_move_child);
+    self.selection = self.is_reading(Column::Items).then_some(Selection::Index(selected_ci));
+    self.update_child_items(new_i)
   }
   pub fn move_content(&mut self, selected_item: ItemInfo) -> anyhow::Result<()> {
     let new_pi = match self.judge_mode() {
@@ -173,6 +279,8 @@ impl App {
     );
     self.items.state.select(Some(0));
     self.parent_items.state.select(new_pi);
+    self.shift_loading(Column::after_move_child);
+    self.selection = None;
 
     Ok(())
   }
@@ -226,10 +334,11 @@ impl App {
       AppMode::Normal => (self.items.items.len() - 1, self.get_current_index()),
       AppMode::Search => (self.search.list.len() - 1, self.get_search_index()),
     };
-    let new_i = if <isize as std::convert::TryInto<usize>>::try_into(last_i as isize - JUMP as isize).is_ok()
-      && old_i.le(&(last_i - JUMP))
+    let jump = self.config.jump();
+    let new_i = if <isize as std::convert::TryInto<usize>>::try_into(last_i as isize - jump as isize).is_ok()
+      && old_i.le(&(last_i - jump))
     {
-      old_i + JUMP
+      old_i + jump
     } else {
       last_i
     };
@@ -249,7 +358,7 @@ impl App {
       AppMode::Normal => self.get_current_index(),
       AppMode::Search => self.get_search_index(),
     };
-    let new_i = old_i.saturating_sub(JUMP);
+    let new_i = old_i.saturating_sub(self.config.jump());
     match self.judge_mode() {
       AppMode::Normal => self.items.select(new_i),
       AppMode::Search => self.search.select(new_i),
@@ -265,7 +374,6 @@ impl App {
     };
 
     let new_grandparent_path = Self::generate_parent_path(&self.grandparent_path);
-    let new_grandparent_items = Self::make_items(&new_grandparent_path)?;
 
     let new_ci = if self.is_contents_in_working_block() {
       None
@@ -281,21 +389,23 @@ impl App {
         }
       }
     };
-    let new_gi = Self::generate_index(&new_grandparent_items, &self.grandparent_path);
 
-    self.wd = new_wd;
+    let old_wd = mem::replace(&mut self.wd, new_wd);
     self.grandparent_path = new_grandparent_path;
     self.search = Search::new();
+    self.walk = None;
+    self.grep = None;
     self.child_items = mem::replace(
       &mut self.items,
-      mem::replace(
-        &mut self.parent_items,
-        mem::replace(&mut self.grandparent_items, StatefulList::with_items_select(new_grandparent_items, new_gi)),
-      ),
+      mem::replace(&mut self.parent_items, mem::replace(&mut self.grandparent_items, Self::loading(Some(0)))),
     );
     self.child_items.state.select(new_ci);
 
-    Ok(())
+    // The parent column may still be read, and becomes the working block once it is
+    self.shift_loading(Column::after_move_parent);
+    self.selection = self.is_reading(Column::Items).then_some(Selection::Path(old_wd));
+    self.load(Column::Grandparent, Self::column_item(&self.grandparent_path))?;
+    self.wait_for(Column::Grandparent)
   }
   pub fn move_previous(&mut self) -> anyhow::Result<()> {
     if self.is_empty_in_working_block() {
@@ -310,87 +420,849 @@ impl App {
     Ok(())
   }
   fn new() -> anyhow::Result<App> {
-    let wd = env::current_dir()?;
-    let items = super::read_items(&wd)?;
-
-    // Initial selection is 0
-    let child_path = match items.first() {
-      Some(item) => {
-        if item.is_dir() {
-          item.get_path().unwrap_or_else(PathBuf::new)
-        } else {
-          PathBuf::new()
+    let config = Config::new()?;
+    let read_options = config.read_options();
+    Self::with_options(env::current_dir()?, config, read_options, Some(Loader::start()))
+  }
+  #[cfg(test)]
+  fn with_wd(wd: PathBuf, config: Config) -> anyhow::Result<App> {
+    let read_options = config.read_options();
+    Self::with_options(wd, config, read_options, None)
+  }
+  fn with_options(
+    wd: PathBuf,
+    config: Config,
+    read_options: ReadOptions,
+    loader: Option<Loader>,
+  ) -> anyhow::Result<App> {
+    let mut app = App {
+      mode: AppMode::Normal,
+      child_items: Self::loading(None),
+      items: Self::loading(Some(0)),
+      parent_items: Self::loading(Some(0)),
+      grandparent_items: Self::loading(Some(0)),
+      wd: PathBuf::new(),
+      grandparent_path: PathBuf::new(),
+      search: Search::new(),
+      picker: None,
+      walk: None,
+      grep: None,
+      grep_restart: None,
+      marks: vec![],
+      pick: None,
+      piped: false,
+      clipboard: None,
+      journal_path: Journal::output_path().ok(),
+      undo: vec![],
+      prompt: None,
+      status: None,
+      highlighter: Highlighter::default(),
+      loader,
+      selection: None,
+      read_options,
+      config,
+    };
+    app.jump(wd)?;
+
+    Ok(app)
+  }
+  /// Re-read all lists, keeping the selections by path
+  /// The old lists are shown until the new ones are read.
+  pub fn reload(&mut self) -> anyhow::Result<()> {
+    self.load(Column::Grandparent, Self::column_item(&self.grandparent_path))?;
+    self.load(Column::Parent, Self::column_item(Self::generate_parent_path(&self.wd)))?;
+
+    // The working block of file contents is kept as it is
+    if self.wd.is_file() {
+      self.update_search_effect()?;
+    } else {
+      self.load(Column::Items, Self::column_item(&self.wd))?;
+    }
+    self.finish_loading(Some(LOAD_WAIT))
+  }
+  pub fn toggle_hidden(&mut self) -> anyhow::Result<()> {
+    self.read_options.show_hidden = !self.read_options.show_hidden;
+    if self.walk.is_some() {
+      self.walk = Some(self.start_walk());
+    }
+    if self.grep.is_some() {
+      self.grep = Some(self.start_grep());
+    }
+    self.reload()
+  }
+  pub fn cycle_sort(&mut self) -> anyhow::Result<()> {
+    self.read_options.sort = self.read_options.sort.next();
+    self.reload()
+  }
+  pub fn toggle_sort_reverse(&mut self) -> anyhow::Result<()> {
+    self.read_options.reverse = !self.read_options.reverse;
+    self.reload()
+  }
+  fn start_walk(&self) -> Walk {
+    Walk::start(self.wd.clone(), self.config.walk_options(self.read_options.show_hidden))
+  }
+  /// Start or stop searching all subdirectories of `wd`
+  pub fn toggle_recursive(&mut self) -> anyhow::Result<()> {
+    if self.walk.is_some() {
+      self.walk = None;
+    } else if self.wd.is_dir() {
+      self.walk = Some(self.start_walk());
+      self.grep = None;
+      self.mode = AppMode::Search;
+    }
+    self.search.select(0);
+    self.update_search_effect()
+  }
+  /// Mark the selected item, or unmark it if it is marked, and move to the next one
+  pub fn toggle_mark(&mut self) -> anyhow::Result<()> {
+    // File contents have no path to mark
+    let Ok(path) = self.get_selected_filepath() else {
+      return Ok(());
+    };
+    match self.marks.iter().position(|mark| *mark == path) {
+      Some(i) => {
+        self.marks.remove(i);
+      }
+      None => self.marks.push(path),
+    }
+    self.move_next()
+  }
+  /// The marked paths, or the selected one if nothing is marked
+  pub fn marked_paths(&self) -> Result<Vec<PathBuf>, AppError> {
+    if self.marks.is_empty() { Ok(vec![self.get_selected_filepath()?]) } else { Ok(self.marks.clone()) }
+  }
+  /// Print the marked paths, or the selected one, once the TUI is closed.
+  /// `None` if there is nothing to print, which is shown in the status line instead.
+  pub fn print_marked(&mut self) -> Option<Action> {
+    match self.marked_paths() {
+      Ok(paths) => Some(Action::PrintMany(paths, self.pick.unwrap_or_default())),
+      Err(_) => {
+        self.status = Some(Status::Info("Nothing to print".into()));
+        None
+      }
+    }
+  }
+  /// Copy or cut the marked items, or the selected one, to be pasted with `paste`
+  pub fn yank(&mut self, cut: bool) -> anyhow::Result<()> {
+    let Ok(paths) = self.marked_paths() else {
+      return Ok(());
+    };
+    let verb = if cut { "Cut" } else { "Yanked" };
+    self.status = Some(Status::Info(format!("{verb} {}", describe(&paths))));
+    self.clipboard = Some(Clipboard { paths, cut });
+    self.marks.clear();
+    Ok(())
+  }
+  /// Copy or move the yanked items into `wd`, a cut is pasted only once
+  pub fn paste(&mut self) -> anyhow::Result<()> {
+    let Some(mut clipboard) = self.clipboard.take() else {
+      self.status = Some(Status::Info("Nothing to paste".into()));
+      return Ok(());
+    };
+    let result = self.paste_into_wd(&clipboard);
+    if !clipboard.cut {
+      self.clipboard = Some(clipboard);
+    } else if result.is_err() {
+      // The items not moved yet can be pasted again
+      clipboard.paths.retain(|path| fileop::exists(path));
+      self.clipboard = Some(clipboard).filter(|clipboard| !clipboard.paths.is_empty());
+    }
+    self.finish_operation(result)
+  }
+  fn paste_into_wd(&mut self, clipboard: &Clipboard) -> Result<(String, Option<PathBuf>), AppError> {
+    if !self.wd.is_dir() {
+      return Err(FileSystemError::InvalidPath(self.generate_wd_str()).into());
+    }
+    let mut pasted = None;
+    self.undo.push(vec![]);
+    for path in &clipboard.paths {
+      let dest = if !clipboard.cut {
+        fileop::copy_into(path, &self.wd)?
+      } else if path.parent() == Some(self.wd.as_path()) {
+        // Already here
+        path.clone()
+      } else {
+        let dest = fileop::move_into(path, &self.wd)?;
+        self.move_marks(path, &dest);
+        self.journal(path, &dest)?;
+        dest
+      };
+      pasted = Some(dest);
+    }
+    let verb = if clipboard.cut { "Moved" } else { "Copied" };
+    Ok((format!("{verb} {}", describe(&clipboard.paths)), pasted))
+  }
+  /// Record a rename or move to be undone later, with the others of the last group
+  fn journal(&mut self, from: &Path, to: &Path) -> Result<(), AppError> {
+    if let Some(path) = &self.journal_path {
+      let id = Journal::record(path, from, to)?;
+      match self.undo.last_mut() {
+        Some(group) => group.push(id),
+        None => self.undo.push(vec![id]),
+      }
+    }
+    Ok(())
+  }
+  /// Move back the items renamed or moved by the last operation of this session
+  pub fn undo(&mut self) -> anyhow::Result<()> {
+    self.undo.retain(|group| !group.is_empty());
+    let (Some(group), Some(path)) = (self.undo.pop(), self.journal_path.clone()) else {
+      self.status = Some(Status::Info("Nothing to undo".into()));
+      return Ok(());
+    };
+    let result = self.revert(&path, &group);
+    let count = group.len();
+    // Nothing is moved back on failure, which can be tried again once the collision is resolved
+    if result.is_err() {
+      self.undo.push(group);
+    }
+    let result = result.map(|from| match (count, from) {
+      (1, Some(from)) => (format!("Moved back to {}", from.display()), Some(from)),
+      (_, from) => (format!("Moved back {count} items"), from),
+    });
+    self.finish_operation(result)
+  }
+  /// Move back the changes `ids` of the journal at `path` together,
+  /// so that items renamed to each other's paths, such as a swap, are moved through temporary names.
+  /// Returns the first path moved back.
+  fn revert(&mut self, path: &Path, ids: &[u64]) -> Result<Option<PathBuf>, AppError> {
+    let mut journal = Journal::load(path)?;
+    let mut renames = vec![];
+    for id in ids.iter().rev() {
+      let entry = journal.find_unreverted(*id)?;
+      renames.push(Rename { from: entry.to.clone(), to: entry.from.clone(), cycle: false });
+    }
+    for (from, to) in rename::apply(&renames)? {
+      self.move_marks(&from, &to);
+    }
+    Journal::set_reverted_saved(path, ids)?;
+    Ok(renames.last().map(|r| r.to.clone()))
+  }
+  /// Marks of `from` and the paths below it follow the move to `to`
+  fn move_marks(&mut self, from: &Path, to: &Path) {
+    for mark in self.marks.iter_mut() {
+      if let Ok(rest) = mark.strip_prefix(from) {
+        *mark = if rest.as_os_str().is_empty() { to.into() } else { to.join(rest) };
+      }
+    }
+  }
+  /// Rename the selected item with its name as the input
+  pub fn open_rename(&mut self) {
+    if let Ok(path) = self.get_selected_filepath() {
+      let name = path.file_name().unwrap_or_default().to_string_lossy().into();
+      self.prompt = Some(Prompt::new(PromptKind::Rename(path), "Rename: ".into(), name));
+    }
+  }
+  pub fn open_new_file(&mut self) {
+    if self.wd.is_dir() {
+      self.prompt = Some(Prompt::new(PromptKind::NewFile, "New file: ".into(), String::new()));
+    }
+  }
+  pub fn open_new_dir(&mut self) {
+    if self.wd.is_dir() {
+      self.prompt = Some(Prompt::new(PromptKind::NewDir, "New directory: ".into(), String::new()));
+    }
+  }
+  /// Ask before deleting the marked items, or the selected one
+  pub fn open_delete(&mut self) {
+    let Ok(paths) = self.marked_paths() else {
+      return;
+    };
+    let label = if self.config.is_trash() {
+      format!("Move {} to the trash? (y/n) ", describe(&paths))
+    } else {
+      format!("Delete {} permanently? (y/n) ", describe(&paths))
+    };
+    self.prompt = Some(Prompt::new(PromptKind::Delete(paths), label, String::new()));
+  }
+  /// Run the file operation of the prompt with its input
+  pub fn confirm_prompt(&mut self) -> anyhow::Result<()> {
+    let Some(prompt) = self.prompt.take() else {
+      return Ok(());
+    };
+    let input = prompt.input.trim();
+    let result = match prompt.kind {
+      PromptKind::Rename(path) => self.rename(&path, input),
+      PromptKind::NewFile => fileop::create_file(&self.wd, input)
+        .map(|path| (format!("Created {input}"), Some(path)))
+        .map_err(AppError::from),
+      PromptKind::NewDir => {
+        fileop::create_dir(&self.wd, input).map(|path| (format!("Created {input}"), Some(path))).map_err(AppError::from)
+      }
+      PromptKind::Delete(paths) => self.delete(&paths),
+      PromptKind::BulkRename(renames) => self.bulk_rename(&renames),
+      // Run by `run` with the terminal suspended
+      PromptKind::Shell => return Ok(()),
+    };
+    self.finish_operation(result)
+  }
+  pub fn open_shell(&mut self) {
+    self.prompt = Some(Prompt::new(PromptKind::Shell, "!".into(), String::new()));
+  }
+  /// `%s` is replaced with the selected path, `%d` with `wd` and `%%` with `%`, quoted for the shell
+  pub fn expand_command(&self, command: &str) -> String {
+    let quote = |path: &Path| format!("'{}'", path.to_string_lossy().replace('\'', r"'\''"));
+    let mut expanded = String::new();
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+      if c != '%' {
+        expanded.push(c);
+        continue;
+      }
+      match chars.next() {
+        Some('s') => expanded.push_str(&quote(&self.get_selected_filepath().unwrap_or_else(|_| self.wd.clone()))),
+        Some('d') => expanded.push_str(&quote(&self.wd)),
+        Some('%') => expanded.push('%'),
+        Some(c) => {
+          expanded.push('%');
+          expanded.push(c);
         }
+        None => expanded.push('%'),
+      }
+    }
+    expanded
+  }
+  /// Where commands are run, the directory of the file if its contents are shown
+  pub fn command_dir(&self) -> &Path {
+    if self.wd.is_dir() { &self.wd } else { self.wd.parent().unwrap_or(&self.wd) }
+  }
+  /// Re-read all lists after an external command, moving up if `wd` is gone
+  pub fn refresh(&mut self) -> anyhow::Result<()> {
+    self.highlighter.clear();
+    if self.wd.exists() {
+      return self.reload();
+    }
+    match self.wd.ancestors().find(|dir| dir.is_dir()).map(Path::to_path_buf) {
+      Some(dir) => self.jump(dir),
+      None => Ok(()),
+    }
+  }
+  /// The file to edit and the line from `1` to open it at: the selected line of file contents or grep, or the selected file.
+  /// `None` if a directory is selected.
+  pub fn edit_target(&self) -> Result<Option<(PathBuf, Option<usize>)>, AppError> {
+    let item = self.get_selected_item()?;
+    Ok(match &item.item {
+      Item::Content(_) if self.wd.is_file() => Some((self.wd.clone(), item.index.map(|i| i + 1))),
+      Item::Grep(m) => Some((m.path.clone(), Some(m.line + 1))),
+      _ if item.is_file() => Some((self.get_selected_filepath()?, None)),
+      _ => None,
+    })
+  }
+  /// The file whose contents are shown, in the working block if `true` or in the preview
+  pub fn shown_file(&self) -> Option<(PathBuf, bool)> {
+    if self.wd.is_file() {
+      return Some((self.wd.clone(), true));
+    }
+    match self.child_items.items.first().map(|item| &item.item) {
+      Some(Item::Content(_)) => self.get_selected_filepath().ok().map(|path| (path, false)),
+      _ => None,
+    }
+  }
+  /// Re-read the file contents of the working block after they are edited, keeping the selected line
+  pub fn reload_contents(&mut self) -> anyhow::Result<()> {
+    if !self.wd.is_file() {
+      return self.refresh();
+    }
+    self.highlighter.clear();
+    self.load(Column::Items, Self::column_item(&self.wd))?;
+    self.finish_loading(Some(LOAD_WAIT))
+  }
+  /// Edit the paths of the marked items, or all items of the working block, with `edit`.
+  /// The renames are previewed to be confirmed, unless the edited paths are invalid.
+  pub fn open_bulk_rename<F>(&mut self, edit: F) -> anyhow::Result<()>
+  where
+    F: FnOnce(&str) -> Result<String, AppError>,
+  {
+    let paths = if self.marks.is_empty() {
+      let items = match self.judge_mode() {
+        AppMode::Normal => &self.items.items,
+        AppMode::Search => &self.search.list,
+      };
+      items.iter().filter_map(|item| item.get_path()).collect()
+    } else {
+      self.marks.clone()
+    };
+    if paths.is_empty() {
+      return Ok(());
+    }
+    let renames = rename::to_lines(&paths, &self.wd)
+      .map_err(AppError::from)
+      .and_then(|text| edit(&text))
+      .and_then(|edited| rename::plan(&paths, &edited, &self.wd));
+    match renames {
+      Ok(renames) if renames.is_empty() => self.status = Some(Status::Info("Nothing renamed".into())),
+      Ok(renames) => {
+        let label = format!("Rename {}? (y/n) ", describe(&renames.iter().map(|r| r.from.clone()).collect::<Vec<_>>()));
+        self.prompt = Some(Prompt::new(PromptKind::BulkRename(renames), label, String::new()));
       }
-      None => PathBuf::new(),
+      Err(e) => self.status = Some(Status::Error(e.to_string())),
+    }
+    Ok(())
+  }
+  fn bulk_rename(&mut self, renames: &[Rename]) -> Result<(String, Option<PathBuf>), AppError> {
+    // The temporary names of a swap are not journaled, only the renames themselves
+    for (from, to) in rename::apply(renames)? {
+      self.move_marks(&from, &to);
+    }
+    self.undo.push(vec![]);
+    for r in renames {
+      self.journal(&r.from, &r.to)?;
+    }
+    let paths = renames.iter().map(|r| r.from.clone()).collect::<Vec<_>>();
+    Ok((format!("Renamed {}", describe(&paths)), renames.first().map(|r| r.to.clone())))
+  }
+  fn rename(&mut self, path: &Path, name: &str) -> Result<(String, Option<PathBuf>), AppError> {
+    let dest = fileop::rename(path, name)?;
+    if dest != path {
+      self.move_marks(path, &dest);
+      self.undo.push(vec![]);
+      self.journal(path, &dest)?;
+    }
+    Ok((format!("Renamed to {name}"), Some(dest)))
+  }
+  fn delete(&mut self, paths: &[PathBuf]) -> Result<(String, Option<PathBuf>), AppError> {
+    // The nearest item left after the selected one is selected next
+    let (items, i) = match self.judge_mode() {
+      AppMode::Normal => (&self.items.items, self.get_current_index()),
+      AppMode::Search => (&self.search.list, self.get_search_index()),
     };
-    let parent_path = Self::generate_parent_path(&wd);
-    let grandparent_path = Self::generate_parent_path(&parent_path);
-    let parent_items = Self::make_items(&parent_path)?;
-    let grandparent_items = Self::make_items(&grandparent_path)?;
-    let pi = Self::generate_index(&parent_items, &wd);
-    let gi = Self::generate_index(&grandparent_items, &parent_path);
+    let i = i.min(items.len());
+    let next = items[i..]
+      .iter()
+      .chain(items[..i].iter().rev())
+      .filter_map(|item| item.get_path())
+      .find(|path| !paths.iter().any(|deleted| path.starts_with(deleted)));
 
-    let mut app = App {
-      mode: AppMode::Normal,
-      child_items: StatefulList::with_items_option(Self::make_items(child_path)?, None),
-      items: StatefulList::with_items(items),
-      parent_items: StatefulList::with_items(parent_items),
-      grandparent_items: StatefulList::with_items(grandparent_items),
-      wd,
-      grandparent_path,
-      search: Search::new(),
-      config: Config::new()?,
+    let trash_dir = if self.config.is_trash() { Some(fileop::trash_dir()?) } else { None };
+    for path in paths {
+      match &trash_dir {
+        Some(trash_dir) => fileop::trash(path, trash_dir).map(|_| ())?,
+        None => fileop::remove(path)?,
+      }
+      self.marks.retain(|mark| !mark.starts_with(path));
+    }
+    let verb = if trash_dir.is_some() { "Trashed" } else { "Deleted" };
+    Ok((format!("{verb} {}", describe(paths)), next))
+  }
+  /// Show the result in the status line, and re-read the lists selecting the path of the result
+  fn finish_operation(&mut self, result: Result<(String, Option<PathBuf>), AppError>) -> anyhow::Result<()> {
+    let select = match result {
+      Ok((message, select)) => {
+        self.status = Some(Status::Info(message));
+        select
+      }
+      Err(e) => {
+        self.status = Some(Status::Error(e.to_string()));
+        None
+      }
+    };
+    self.reload()?;
+    match select {
+      Some(path) => self.select_path(&path),
+      None => Ok(()),
+    }
+  }
+  /// Select the item of `path` in the working block, if it is there
+  fn select_path(&mut self, path: &Path) -> anyhow::Result<()> {
+    if self.is_reading(Column::Items) {
+      self.selection = Some(Selection::Path(path.into()));
+      return Ok(());
+    }
+    let items = match self.judge_mode() {
+      AppMode::Normal => &self.items.items,
+      AppMode::Search => &self.search.list,
+    };
+    let Some(i) = items.iter().position(|item| item.get_path().is_some_and(|p| p == path)) else {
+      return Ok(());
+    };
+    match self.judge_mode() {
+      AppMode::Normal => self.items.select(i),
+      AppMode::Search => self.search.select(i),
+    }
+    self.update_child_items(i)
+  }
+  fn start_grep(&self) -> Grep {
+    let options = self.config.walk_options(self.read_options.show_hidden);
+    Grep::start(self.wd.clone(), &self.search.text, options, self.config.grep_max_size())
+  }
+  /// Start or stop searching inside the files below `wd`
+  pub fn toggle_grep(&mut self) -> anyhow::Result<()> {
+    if self.grep.is_some() {
+      self.grep = None;
+    } else if self.wd.is_dir() {
+      self.grep = Some(self.start_grep());
+      self.walk = None;
+      self.mode = AppMode::Search;
+    }
+    self.search.select(0);
+    self.update_search_effect()
+  }
+  /// Take the results found by the recursive search or grep so far
+  /// Only the new results are scored, and the preview is read again only if the selected item changes.
+  pub fn poll_walk(&mut self) -> anyhow::Result<()> {
+    if self.grep.is_some() && self.grep_restart.is_some_and(|typed| typed.elapsed() >= GREP_DEBOUNCE) {
+      self.grep = Some(self.start_grep());
+      return self.update_search_effect();
+    }
+    let (from, received) = match (self.walk.as_mut(), self.grep.as_mut()) {
+      (Some(walk), _) => (walk.found.len(), walk.receive()),
+      (_, Some(grep)) => (grep.walk.found.len(), grep.walk.receive()),
+      (None, None) => return Ok(()),
     };
+    if !received {
+      return Ok(());
+    }
+    let selected = self.search.list.get(self.get_search_index()).map(|item| item.item.clone());
 
-    app.parent_items.select(pi);
-    app.grandparent_items.select(gi);
+    // Earlier results come first among the same scores, as in a stable sort
+    let old = mem::take(&mut self.search.scores).into_iter().zip(mem::take(&mut self.search.list));
+    let mut new = self.score_found(from).into_iter().peekable();
+    let mut merged = Vec::with_capacity(MAX_WALK_RESULTS);
+    for (score, item) in old {
+      while let Some(next) = new.next_if(|(s, _)| *s > score) {
+        merged.push(next);
+      }
+      merged.push((score, item));
+    }
+    merged.extend(new);
+    merged.truncate(MAX_WALK_RESULTS);
+    (self.search.scores, self.search.list) = merged.into_iter().unzip();
 
-    Ok(app)
+    if self.search.state.selected().is_some_and(|i| i >= self.search.list.len()) {
+      self.search.select(self.search.list.len().saturating_sub(1));
+    }
+    let i = self.get_search_index();
+    if self.judge_mode() == AppMode::Search && self.search.list.get(i).map(|item| &item.item) != selected.as_ref() {
+      self.update_child_items(i)?;
+    }
+    Ok(())
+  }
+  /// Rebuild all lists around the selected directory of the recursive search, or the directory of the selected file.
+  /// A line found by grep is selected in the contents of its file.
+  pub fn jump_to_result(&mut self) -> anyhow::Result<()> {
+    if self.search.list.is_empty() {
+      return Ok(());
+    }
+    let selected_item = self.get_selected_item()?;
+    let path = self.get_selected_filepath()?;
+    if path.is_dir() {
+      return self.jump(path);
+    }
+    if let Item::Grep(m) = selected_item.item {
+      return self.jump_selecting(path, Some(Selection::Line(m)));
+    }
+    match path.parent() {
+      Some(parent) => self.jump_selecting(parent.into(), Some(Selection::Path(path))),
+      None => Ok(()),
+    }
+  }
+  /// A history file that fails to load is shown in the status line
+  pub fn open_history(&mut self) -> anyhow::Result<()> {
+    let history = match History::load() {
+      Ok(history) => history,
+      Err(e) => {
+        self.status = Some(Status::Error(e.to_string()));
+        return Ok(());
+      }
+    };
+    let entries = history
+      .recent()
+      .into_iter()
+      .filter(|visit| visit.path.is_dir())
+      .map(|visit| PickerEntry {
+        name: String::new(),
+        label: format!("{:>4}  {}", visit.count, visit.path.display()),
+        path: visit.path,
+      })
+      .collect();
+    self.picker = Some(Picker::with_entries(PickerKind::History, entries));
+    self.search = Search::new();
+    self.update_search_effect()
+  }
+  pub fn open_bookmarks(&mut self) -> anyhow::Result<()> {
+    let bookmarks = match Bookmarks::load() {
+      Ok(bookmarks) => bookmarks,
+      Err(e) => {
+        self.status = Some(Status::Error(e.to_string()));
+        return Ok(());
+      }
+    };
+    let entries = bookmarks
+      .bookmarks
+      .into_iter()
+      .map(|(name, path)| PickerEntry { label: format!("{name}  {}", path.display()), name, path })
+      .collect();
+    self.picker = Some(Picker::with_entries(PickerKind::Bookmark, entries));
+    self.search = Search::new();
+    self.update_search_effect()
+  }
+  /// A bookmark file that fails to load or save is shown in the status line
+  pub fn add_bookmark(&mut self) {
+    let result = Bookmarks::load().and_then(|mut bookmarks| {
+      bookmarks.add_path(&self.wd);
+      bookmarks.save()
+    });
+    if let Err(e) = result {
+      self.status = Some(Status::Error(e.to_string()));
+    }
+  }
+  pub fn remove_bookmark(&mut self) {
+    let result = Bookmarks::load().and_then(|mut bookmarks| match bookmarks.find_name(&self.wd) {
+      Some(name) => {
+        bookmarks.remove(&name);
+        bookmarks.save()
+      }
+      None => Ok(()),
+    });
+    if let Err(e) = result {
+      self.status = Some(Status::Error(e.to_string()));
+    }
+  }
+  /// Remove the bookmark selected in the bookmark list
+  pub fn remove_selected_bookmark(&mut self) {
+    let Some(picker) = self.picker.as_mut().filter(|picker| picker.kind == PickerKind::Bookmark) else {
+      return;
+    };
+    let Some(name) = picker.selected_entry().map(|entry| entry.name.clone()) else {
+      return;
+    };
+    let result = Bookmarks::load().and_then(|mut bookmarks| {
+      bookmarks.remove(&name);
+      bookmarks.save()
+    });
+    match result {
+      Ok(()) => picker.entries.retain(|entry| entry.name != name),
+      Err(e) => self.status = Some(Status::Error(e.to_string())),
+    }
+    self.update_picker_filter();
   }
-  pub fn search_sort_to_vec(&self) -> Vec<ItemInfo> {
-    self
+  pub fn close_picker(&mut self) -> anyhow::Result<()> {
+    self.picker = None;
+    self.search = Search::new();
+    self.update_search_effect()
+  }
+  pub fn update_picker_filter(&mut self) {
+    if let Some(picker) = self.picker.as_mut() {
+      picker.filter(&self.search.text);
+    }
+  }
+  /// File contents are kept in order if they match the query,
+  /// files are ranked by the score of their names (see `Query::find`).
+  /// In the recursive search, paths relative to `wd` are ranked instead,
+  /// and grep keeps the lines in the order they are found.
+  pub fn search_sort_to_vec(&self) -> Vec<(i64, ItemInfo)> {
+    if self.grep.is_some() || self.walk.is_some() {
+      return self.score_found(0);
+    }
+    let mut scored = self
       .items
       .items
       .iter()
-      .filter_map(|item| -> Option<ItemInfo> {
+      .filter_map(|item| -> Option<(i64, ItemInfo)> {
         if let Item::Content(s) = &item.item {
-          if s.contains(&self.search.text) { Some(item.clone()) } else { None }
-        } else if item
-          .get_path()
-          .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
-          .is_some_and(|name| name.contains(&self.search.text))
-        {
-          Some(item.clone())
+          if self.search.query.is_match_line(s) { Some((0, item.clone())) } else { None }
         } else {
-          None
+          let name = item.generate_filename()?;
+          self.search.query.find(&name).map(|m| (m.score, item.clone()))
         }
       })
+      .collect::<Vec<_>>();
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    scored
+  }
+  /// The results of the recursive search or grep from the `from`th one found, ranked
+  fn score_found(&self, from: usize) -> Vec<(i64, ItemInfo)> {
+    if let Some(grep) = &self.grep {
+      return grep.walk.found[from..]
+        .iter()
+        .take(MAX_WALK_RESULTS)
+        .map(|m| (0, ItemInfo { item: Item::Grep(m.clone()), index: None }))
+        .collect();
+    }
+    let Some(walk) = &self.walk else {
+      return vec![];
+    };
+    let mut scored = walk.found[from..]
+      .iter()
+      .filter_map(|path| self.search.query.find(&walk.relative(path).to_string_lossy()).map(|m| (m.score, path)))
+      .collect::<Vec<_>>();
+    // Stable, so that shallow paths come first among the same scores
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    scored
+      .into_iter()
+      .take(MAX_WALK_RESULTS)
+      .map(|(score, path)| (score, ItemInfo { item: Item::Path(ItemPath::from_path(path.clone())), index: None }))
       .collect()
   }
   fn update_child_items(&mut self, index: usize) -> anyhow::Result<()> {
     if self.is_empty_in_working_block() {
+      self.cancel_loading(Column::Child);
       self.child_items = StatefulList::with_items_option(vec![], None);
       return Ok(());
     }
+    // Read once the working block is
+    if self.is_reading(Column::Items) {
+      self.cancel_loading(Column::Child);
+      self.child_items = Self::loading(self.child_items.state.selected());
+      return Ok(());
+    }
 
-    let ci = self.child_items.state.selected();
-
-    let items = match self.judge_mode() {
-      AppMode::Normal => self.get_items(),
-      AppMode::Search => self.get_search_list(),
-    };
+    let item = match self.judge_mode() {
+      AppMode::Normal => self.items.items.get(index),
+      AppMode::Search => self.search.list.get(index),
+    }
+    .cloned()
+    .unwrap_or_else(ItemInfo::default);
 
-    self.child_items =
-      StatefulList::with_items_option(items.get(index).unwrap_or(&ItemInfo::default()).generate_child_items()?, ci);
-    if items[index].is_file() {
+    self.load(Column::Child, item)?;
+    self.wait_for(Column::Child)?;
+    if self.is_reading(Column::Child) {
+      self.child_items = Self::loading(self.child_items.state.selected());
+    }
+    Ok(())
+  }
+  fn set_child_items(&mut self, item: &ItemInfo, children: Vec<ItemInfo>) {
+    let ci = self.child_items.state.selected();
+    self.child_items = StatefulList::with_items_option(children, ci);
+    if let Item::Grep(m) = &item.item {
+      self.child_items.select(m.line.min(self.child_items.items.len().saturating_sub(1)));
+    } else if item.is_file() {
       self.child_items.unselect();
     }
-
+  }
+  /// The working block read, selecting the item at the path selected before unless `selection` is pending
+  fn set_items(&mut self, mut items: Vec<ItemInfo>) -> anyhow::Result<()> {
+    let i = match self.selection.take() {
+      Some(Selection::Index(i)) => {
+        if i < items.len() {
+          i
+        } else {
+          0
+        }
+      }
+      Some(Selection::Path(path)) => Self::generate_index(&items, path),
+      Some(Selection::Line(m)) => match items.iter().position(|item| item.index == Some(m.line)) {
+        Some(i) => i,
+        None => {
+          // Beyond the lines of the preview if the file is too large, so the matched line follows them
+          items.push(ItemInfo { item: Item::Content(m.text), index: Some(m.line) });
+          items.len() - 1
+        }
+      },
+      None => match self.items.items.get(self.items.selected()).and_then(|item| item.get_path()) {
+        Some(path) => Self::generate_index(&items, path),
+        None => self.items.selected().min(items.len().saturating_sub(1)),
+      },
+    };
+    self.items = StatefulList::with_items_select(items, i);
+    self.update_search_effect()
+  }
+  /// Show the children of `item` read for `column`
+  fn set_column(&mut self, column: Column, item: &ItemInfo, children: Vec<ItemInfo>) -> anyhow::Result<()> {
+    match column {
+      Column::Grandparent => {
+        let gi = Self::generate_index(&children, Self::generate_parent_path(&self.wd));
+        self.grandparent_items = StatefulList::with_items_select(children, gi);
+      }
+      Column::Parent => {
+        let pi = Self::generate_index(&children, &self.wd);
+        self.parent_items = StatefulList::with_items_select(children, pi);
+      }
+      Column::Items => return self.set_items(children),
+      Column::Child => self.set_child_items(item, children),
+    }
+    Ok(())
+  }
+  /// The item whose children are shown in the column of `path`, the contents if it is a file
+  fn column_item<P: AsRef<Path>>(path: P) -> ItemInfo {
+    let path = path.as_ref().to_path_buf();
+    let item = if path.is_file() { Item::Path(ItemPath::File(path)) } else { Item::create_dir(path) };
+    ItemInfo { item, index: None }
+  }
+  /// The placeholder of a column read in the background
+  fn loading(selected: Option<usize>) -> StatefulList {
+    StatefulList::with_items_option(vec![ItemInfo { item: Item::Note("loading…".into()), index: None }], selected)
+  }
+  /// Read the children of `item` for `column`, in the background if there is a loader
+  fn load(&mut self, column: Column, item: ItemInfo) -> anyhow::Result<()> {
+    match self.loader.as_mut() {
+      Some(loader) => {
+        loader.request(column, item, self.read_options);
+        Ok(())
+      }
+      None => {
+        let children = item.generate_child_items(self.read_options)?;
+        self.set_column(column, &item, children)
+      }
+    }
+  }
+  /// If any column is still read in the background `true`
+  pub fn is_loading(&self) -> bool {
+    self.loader.as_ref().is_some_and(|loader| loader.is_loading())
+  }
+  /// If `column` is still read in the background `true`
+  fn is_reading(&self, column: Column) -> bool {
+    self.loader.as_ref().is_some_and(|loader| loader.is_reading(column))
+  }
+  /// Drop the background read of `column`, when it is replaced without it
+  fn cancel_loading(&mut self, column: Column) {
+    if let Some(loader) = self.loader.as_mut() {
+      loader.cancel(column);
+    }
+  }
+  /// Keep the background reads in the columns they are shown in after a move
+  fn shift_loading(&mut self, shift: fn(Column) -> Option<Column>) {
+    if let Some(loader) = self.loader.as_mut() {
+      loader.shift(shift);
+    }
+  }
+  /// Show a column read in the background, waiting for it up to `timeout`. `false` if none is read.
+  fn receive_loaded(&mut self, timeout: Duration) -> anyhow::Result<bool> {
+    let Some(loaded) = self.loader.as_mut().and_then(|loader| loader.receive(timeout)) else {
+      return Ok(false);
+    };
+    match loaded.children {
+      Ok(children) => self.set_column(loaded.column, &loaded.item, children)?,
+      Err(e) => {
+        self.set_column(loaded.column, &loaded.item, vec![])?;
+        self.status = Some(Status::Error(e));
+      }
+    }
+    Ok(true)
+  }
+  /// Wait up to `LOAD_WAIT` for `column`, so that a fast read is shown at once without the placeholder
+  fn wait_for(&mut self, column: Column) -> anyhow::Result<()> {
+    let deadline = Instant::now() + LOAD_WAIT;
+    while self.is_reading(column) && self.receive_loaded(deadline.saturating_duration_since(Instant::now()))? {}
+    Ok(())
+  }
+  /// Show the columns read in the background, waiting for them up to `timeout`, or until they are read if `None`
+  pub fn finish_loading(&mut self, timeout: Option<Duration>) -> anyhow::Result<()> {
+    let deadline = timeout.and_then(|timeout| Instant::now().checked_add(timeout));
+    while self.is_loading() {
+      let timeout = deadline.map_or(Duration::MAX, |deadline| deadline.saturating_duration_since(Instant::now()));
+      if !self.receive_loaded(timeout)? {
+        break;
+      }
+    }
     Ok(())
   }
   pub fn update_search_effect(&mut self) -> anyhow::Result<()> {
-    self.search.list = self.search_sort_to_vec();
+    // Not on every key, `poll_walk` starts grep over once the typing stops
+    self.grep_restart = self.grep.as_ref().filter(|grep| grep.pattern != self.search.text).map(|_| Instant::now());
+    // An invalid query keeps the last results, and grep searches the text as it is
+    let query = if self.grep.is_some() { Ok(Query::default()) } else { Query::parse(&self.search.text) };
+    match query {
+      Ok(query) => {
+        self.search.query = query;
+        self.search.error = None;
+        (self.search.scores, self.search.list) = self.search_sort_to_vec().into_iter().unzip();
+      }
+      Err(e) => self.search.error = Some(e),
+    }
+    if self.search.state.selected().is_some_and(|i| i >= self.search.list.len()) {
+      self.search.select(self.search.list.len().saturating_sub(1));
+    }
 
     let now_i = match self.judge_mode() {
       AppMode::Normal => self.get_current_index(),
@@ -403,39 +1275,116 @@ impl App {
   }
 }
 
-pub fn app() -> anyhow::Result<Action> {
+/// `history_query`: open with the history filtered by it
+/// `pick`: print the marked paths separated by it instead of changing directory
+/// `handoff`: the destination is printed on stdout, as in `cd "$(easychangedirectory --stdout)"`
+pub fn app(history_query: Option<&str>, pick: Option<Separator>, handoff: bool) -> anyhow::Result<Action> {
+  let piped = pick.is_some() || handoff;
+  // Built before the terminal is set up, which is left as it is if the config or the history fails to load
+  let mut app = App::new()?;
+  app.pick = pick;
+  app.piped = piped;
+  if let Some(query) = history_query {
+    app.open_history()?;
+    app.search.text = query.into();
+    app.update_picker_filter();
+  }
+
   // setup terminal
   enable_raw_mode()?;
-  let mut stdout = io::stdout();
-  execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-  let backend = CrosstermBackend::new(stdout);
-  let mut terminal = Terminal::new(backend)?;
+  // stdout is left for the picked paths or the destination, as in `vim $(ed --pick)`
+  let mut writer: Box<dyn Write> = match open_tty().filter(|_| piped) {
+    Some(tty) => Box::new(BufWriter::new(tty)),
+    None if piped => Box::new(io::stderr()),
+    None => Box::new(io::stdout()),
+  };
+  let terminal = execute!(writer, EnterAlternateScreen, EnableMouseCapture)
+    .and_then(|()| Terminal::new(CrosstermBackend::new(writer)));
+  let mut terminal = match terminal {
+    Ok(terminal) => terminal,
+    Err(e) => {
+      disable_raw_mode()?;
+      bail!(e)
+    }
+  };
 
-  let app = App::new()?;
   let action = match super::run(&mut terminal, app) {
     Ok(action) => action,
     Err(e) => {
-      // restore terminal
-      disable_raw_mode()?;
-      execute!(terminal.backend_mut(), LeaveAlternateScreen, DisableMouseCapture)?;
-      terminal.show_cursor()?;
-
+      suspend_terminal(&mut terminal)?;
       bail!(e)
     }
   };
 
-  // restore terminal
+  suspend_terminal(&mut terminal)?;
+
+  if let Action::Change(path) = &action
+    && let Err(e) = History::record(path)
+  {
+    e.eprintln();
+  }
+
+  Ok(action)
+}
+
+/// The terminal itself, for the output that must not go to stdout when it is a pipeline
+pub fn open_tty() -> Option<File> {
+  let path = if cfg!(windows) { "CONOUT$" } else { "/dev/tty" };
+  OpenOptions::new().read(true).write(true).open(path).ok()
+}
+
+/// Restore the terminal for the shell, or for another program run from the TUI
+pub fn suspend_terminal<B: Backend + Write>(terminal: &mut Terminal<B>) -> io::Result<()> {
   disable_raw_mode()?;
   execute!(terminal.backend_mut(), LeaveAlternateScreen, DisableMouseCapture)?;
-  terminal.show_cursor()?;
+  terminal.show_cursor()
+}
 
-  Ok(action)
+/// Back to the TUI after `suspend_terminal`, which is redrawn from scratch
+pub fn resume_terminal<B: Backend + Write>(terminal: &mut Terminal<B>) -> io::Result<()> {
+  enable_raw_mode()?;
+  execute!(terminal.backend_mut(), EnterAlternateScreen, EnableMouseCapture)?;
+  terminal.clear()
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
-  use std::path::PathBuf;
+  use crate::testing::TempDir;
+  use std::{fs, path::PathBuf, process::Command};
+
+  use ratatui::crossterm::terminal::is_raw_mode_enabled;
+
+  #[test]
+  fn test_app_config_error() {
+    let root = TempDir::new("app_config_error");
+    let dir = root.join(env!("CARGO_PKG_NAME"));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("config.toml"), "jump = \"x\"\n").unwrap();
+    // In a process of its own, which reads the config of `root`
+    let output = Command::new(env::current_exe().unwrap())
+      .args(["--exact", "app::_app::tests::app_with_config_error", "--ignored"])
+      .env("XDG_CONFIG_HOME", &*root)
+      .env("ED_TEST_CONFIG_ERROR", "1")
+      .output()
+      .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stdout));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("1 passed"));
+  }
+
+  #[test]
+  #[ignore = "run by test_app_config_error with an invalid config"]
+  fn app_with_config_error() {
+    if env::var_os("ED_TEST_CONFIG_ERROR").is_none() {
+      return;
+    }
+    // The config is read before the terminal is touched
+    let Err(e) = app(None, None, false) else {
+      panic!("The TUI is opened with an invalid config");
+    };
+    assert!(e.to_string().contains("Failed to parse config file"), "{e}");
+    assert!(!is_raw_mode_enabled().unwrap());
+  }
 
   #[test]
   fn test_app_mode() {
@@ -445,7 +1394,7 @@ mod tests {
 
   #[test]
   fn test_make_items_empty_path() {
-    let result = App::make_items("");
+    let result = App::make_items("", ReadOptions::default());
     assert!(result.is_ok());
     let items = result.expect("Failed to create items for empty path");
     assert_eq!(items.len(), 1);
@@ -459,6 +1408,301 @@ mod tests {
     assert_eq!(parent, PathBuf::from("/home/user"));
   }
 
+  /// Poll until the recursive search or grep is done, panicking if it takes too long
+  fn wait_walk(app: &mut App) {
+    let deadline = std::time::Instant::now() + Duration::from_secs(10);
+    while app.is_subtree_searching() {
+      assert!(std::time::Instant::now() < deadline, "the walk did not finish in time");
+      app.poll_walk().unwrap();
+      std::thread::sleep(Duration::from_millis(1));
+    }
+  }
+
+  #[test]
+  fn test_recursive_search() {
+    let root = TempDir::new("recursive_search");
+    fs::create_dir_all(root.join("a/b")).unwrap();
+    fs::create_dir_all(root.join("target/b")).unwrap();
+    fs::write(root.join("a/b/file.txt"), "").unwrap();
+
+    let mut app = App::with_wd(root.to_path_buf(), Config::default()).unwrap();
+    app.toggle_recursive().unwrap();
+    assert_eq!(app.judge_mode(), AppMode::Search);
+    wait_walk(&mut app);
+    let relative = |app: &App| {
+      let walk = app.walk.as_ref().unwrap();
+      app.search.list.iter().map(|item| walk.relative(&item.get_path().unwrap()).to_path_buf()).collect::<Vec<_>>()
+    };
+    assert_eq!(relative(&app), ["a", "a/b", "a/b/file.txt"].map(PathBuf::from));
+
+    app.search.text = "abf".into();
+    app.update_search_effect().unwrap();
+    assert_eq!(relative(&app), [PathBuf::from("a/b/file.txt")]);
+
+    // The file is selected in its directory
+    app.jump_to_result().unwrap();
+    assert!(app.walk.is_none());
+    assert_eq!(app.wd, root.join("a/b"));
+    assert_eq!(app.get_selected_filepath().unwrap(), root.join("a/b/file.txt"));
+  }
+
+  #[test]
+  fn test_recursive_search_merge() {
+    let root = TempDir::new("recursive_search_merge");
+    // More than one batch, received as they are found
+    for i in 0..600 {
+      fs::create_dir_all(root.join(format!("d{}/f{i}", i % 7))).unwrap();
+    }
+
+    let mut app = App::with_wd(root.to_path_buf(), Config::default()).unwrap();
+    app.search.text = "f1".into();
+    app.toggle_recursive().unwrap();
+    wait_walk(&mut app);
+    // The same as ranking all of them at once
+    let merged = app.search.list.iter().map(|item| item.item.clone()).collect::<Vec<_>>();
+    let sorted = app.search_sort_to_vec().into_iter().map(|(_, item)| item.item).collect::<Vec<_>>();
+    assert!(merged.len() > 100);
+    assert_eq!(merged, sorted);
+  }
+
+  #[test]
+  fn test_grep() {
+    let root = TempDir::new("grep_jump");
+    fs::create_dir_all(root.join("a")).unwrap();
+    fs::write(root.join("a/notes.md"), "# notes\n\nsee the manual\n").unwrap();
+
+    let mut app = App::with_wd(root.to_path_buf(), Config::default()).unwrap();
+    app.toggle_grep().unwrap();
+    app.search.text = "manual".into();
+    app.update_search_effect().unwrap();
+    // Started over only once the typing stops
+    assert_eq!(app.grep.as_ref().unwrap().pattern, "");
+    wait_walk(&mut app);
+    assert_eq!(app.grep.as_ref().unwrap().pattern, "manual");
+    assert_eq!(app.search.list.len(), 1);
+    // The matched line is selected in the preview
+    assert_eq!(app.child_items.state.selected(), Some(2));
+    assert_eq!(app.edit_target().unwrap(), Some((root.join("a/notes.md"), Some(3))));
+
+    app.jump_to_result().unwrap();
+    assert!(app.grep.is_none());
+    assert_eq!(app.wd, root.join("a/notes.md"));
+    assert_eq!(app.items.selected(), 2);
+    assert_eq!(app.items.items[2].item, Item::Content("see the manual".into()));
+    assert_eq!(app.edit_target().unwrap(), Some((root.join("a/notes.md"), Some(3))));
+
+    // The same line stays selected after editing
+    fs::write(root.join("a/notes.md"), "# notes\n\nsee the manual page\nend\n").unwrap();
+    app.reload_contents().unwrap();
+    assert_eq!(app.items.selected(), 2);
+    assert_eq!(app.items.items[2].item, Item::Content("see the manual page".into()));
+  }
+
+  #[test]
+  fn test_grep_beyond_preview() {
+    let root = TempDir::new("grep_beyond_preview");
+    fs::write(root.join("long.txt"), format!("{}the end\n", "line\n".repeat(10_000))).unwrap();
+
+    let mut app = App::with_wd(root.to_path_buf(), Config::default()).unwrap();
+    app.search.text = "end".into();
+    app.toggle_grep().unwrap();
+    wait_walk(&mut app);
+    app.jump_to_result().unwrap();
+    // The matched line is shown and edited, though the preview stops before it
+    assert_eq!(app.get_selected_item().unwrap().item, Item::Content("the end".into()));
+    assert_eq!(app.edit_target().unwrap(), Some((root.join("long.txt"), Some(10_001))));
+  }
+
+  #[test]
+  fn test_loading() {
+    let root = TempDir::new("loading");
+    fs::create_dir_all(root.join("a/b")).unwrap();
+    fs::write(root.join("a/b/file.txt"), "").unwrap();
+    fs::write(root.join("c.txt"), "text\n").unwrap();
+    let paths = |list: &StatefulList| list.items.iter().filter_map(|item| item.get_path()).collect::<Vec<_>>();
+
+    let config = Config::default();
+    let read_options = config.read_options();
+    let mut app = App::with_options(root.to_path_buf(), config, read_options, Some(Loader::start())).unwrap();
+    app.finish_loading(None).unwrap();
+    assert!(!app.is_loading());
+    assert_eq!(paths(&app.items), [root.join("a"), root.join("c.txt")]);
+    assert_eq!(app.get_parent_items()[app.parent_items.selected()].get_path(), Some(root.to_path_buf()));
+    app.select_path(&root.join("c.txt")).unwrap();
+    app.select_path(&root.join("a")).unwrap();
+    app.finish_loading(None).unwrap();
+    assert_eq!(paths(&app.child_items), [root.join("a/b")]);
+
+    // The working block is taken from the child column once it is read
+    app.load(Column::Child, App::column_item(root.join("a"))).unwrap();
+    app.child_items = App::loading(None);
+    app.move_child().unwrap();
+    assert_eq!(app.wd, root.join("a"));
+    assert!(app.is_reading(Column::Items));
+    app.finish_loading(None).unwrap();
+    assert_eq!(paths(&app.items), [root.join("a/b")]);
+    assert_eq!(paths(&app.child_items), [root.join("a/b/file.txt")]);
+
+    // A read replaced by the parent directory is dropped
+    app.load(Column::Child, App::column_item(root.join("a/b"))).unwrap();
+    app.move_parent().unwrap();
+    assert!(!app.is_reading(Column::Child));
+    app.finish_loading(None).unwrap();
+    assert_eq!(paths(&app.child_items), [root.join("a/b")]);
+    assert_eq!(app.get_selected_filepath().unwrap(), root.join("a"));
+
+    // The path is selected once the working block is read
+    app.jump_selecting(root.join("a/b"), Some(Selection::Path(root.join("a/b/file.txt")))).unwrap();
+    app.finish_loading(None).unwrap();
+    assert_eq!(app.get_selected_filepath().unwrap(), root.join("a/b/file.txt"));
+    assert_eq!(app.get_parent_items()[app.parent_items.selected()].get_path(), Some(root.join("a/b")));
+  }
+
+  #[test]
+  fn test_query() {
+    let root = TempDir::new("query");
+    for name in ["libfoo.so", "libfoo.a", "foo.rs", "bar.rs"] {
+      fs::write(root.join(name), "").unwrap();
+    }
+    let names = |app: &App| app.search.list.iter().filter_map(|item| item.generate_filename()).collect::<Vec<_>>();
+
+    let mut app = App::with_wd(root.to_path_buf(), Config::default()).unwrap();
+    app.search.text = "*.rs".into();
+    app.update_search_effect().unwrap();
+    assert_eq!(names(&app), ["bar.rs", "foo.rs"]);
+
+    app.search.text = "re:^lib.*\\.so$".into();
+    app.update_search_effect().unwrap();
+    assert_eq!(names(&app), ["libfoo.so"]);
+
+    // The results stay while the regex is invalid
+    app.search.text = "re:^lib(".into();
+    app.update_search_effect().unwrap();
+    assert_eq!(app.search.error.as_deref(), Some("error: unclosed group"));
+    assert_eq!(names(&app), ["libfoo.so"]);
+  }
+
+  #[test]
+  fn test_file_operations() {
+    let root = TempDir::new("file_operations");
+    fs::create_dir_all(root.join("dest")).unwrap();
+    fs::write(root.join("a.txt"), "a").unwrap();
+
+    let mut app = App::with_wd(root.to_path_buf(), Config::default()).unwrap();
+    app.journal_path = Some(root.join("journal.json"));
+    app.select_path(&root.join("a.txt")).unwrap();
+    app.yank(false).unwrap();
+    app.jump(root.join("dest")).unwrap();
+    app.paste().unwrap();
+    assert_eq!(app.status, Some(Status::Info("Copied a.txt".into())));
+    assert_eq!(app.get_selected_filepath().unwrap(), root.join("dest/a.txt"));
+
+    // The new name is selected
+    app.open_rename();
+    app.prompt.as_mut().unwrap().input = "b.txt".into();
+    app.confirm_prompt().unwrap();
+    assert!(app.prompt.is_none());
+    assert_eq!(app.get_selected_filepath().unwrap(), root.join("dest/b.txt"));
+
+    app.open_new_dir();
+    app.prompt.as_mut().unwrap().input = "b.txt".into();
+    app.confirm_prompt().unwrap();
+    assert!(matches!(app.status, Some(Status::Error(_))));
+
+    app.open_new_dir();
+    app.prompt.as_mut().unwrap().input = "c".into();
+    app.confirm_prompt().unwrap();
+    assert_eq!(app.get_selected_filepath().unwrap(), root.join("dest/c"));
+    assert_eq!(app.items.items.len(), 2);
+
+    // The rename is undone, but not over a new file with the old name
+    fs::write(root.join("dest/a.txt"), "").unwrap();
+    app.undo().unwrap();
+    assert!(matches!(app.status, Some(Status::Error(_))));
+    fs::remove_file(root.join("dest/a.txt")).unwrap();
+    app.undo().unwrap();
+    assert_eq!(app.get_selected_filepath().unwrap(), root.join("dest/a.txt"));
+    assert!(!root.join("dest/b.txt").exists());
+    app.undo().unwrap();
+    assert_eq!(app.status, Some(Status::Info("Nothing to undo".into())));
+  }
+
+  #[test]
+  fn test_bulk_rename() {
+    let root = TempDir::new("bulk_rename");
+    for name in ["a", "b", "c"] {
+      fs::write(root.join(name), name).unwrap();
+    }
+
+    let mut app = App::with_wd(root.to_path_buf(), Config::default()).unwrap();
+    app.journal_path = Some(root.join("journal.json"));
+    app
+      .open_bulk_rename(|text| {
+        assert_eq!(text, "a\nb\nc\n");
+        Ok("b\na\nd\n".into())
+      })
+      .unwrap();
+    let Some(PromptKind::BulkRename(renames)) = app.prompt.as_ref().map(|prompt| &prompt.kind) else {
+      panic!("the renames are previewed");
+    };
+    assert_eq!(renames.len(), 3);
+    // Nothing is renamed before it is confirmed
+    assert_eq!(fs::read_to_string(root.join("a")).unwrap(), "a");
+
+    app.confirm_prompt().unwrap();
+    assert_eq!(app.status, Some(Status::Info("Renamed 3 items".into())));
+    assert_eq!(fs::read_to_string(root.join("a")).unwrap(), "b");
+    assert_eq!(fs::read_to_string(root.join("d")).unwrap(), "c");
+
+    // Only the renames are journaled, and all of them are undone at once
+    let journal = Journal::load(&root.join("journal.json")).unwrap();
+    let mut changes = journal.entries.iter().map(|entry| (entry.from.clone(), entry.to.clone())).collect::<Vec<_>>();
+    changes.sort();
+    let pair = |from: &str, to: &str| (root.join(from), root.join(to));
+    assert_eq!(changes, [pair("a", "b"), pair("b", "a"), pair("c", "d")]);
+    app.undo().unwrap();
+    assert_eq!(fs::read_to_string(root.join("a")).unwrap(), "a");
+    assert_eq!(fs::read_to_string(root.join("b")).unwrap(), "b");
+    assert_eq!(fs::read_to_string(root.join("c")).unwrap(), "c");
+    assert!(Journal::load(&root.join("journal.json")).unwrap().entries.iter().all(|entry| entry.reverted));
+
+    app.open_bulk_rename(|_| Ok("b\nb\nc\n".into())).unwrap();
+    assert!(app.prompt.is_none());
+    assert!(matches!(app.status, Some(Status::Error(_))));
+  }
+
+  #[test]
+  fn test_print_marked() {
+    let root = TempDir::new("print_marked");
+    fs::create_dir_all(root.join("empty")).unwrap();
+    fs::write(root.join("a.txt"), "").unwrap();
+
+    let mut app = App::with_wd(root.join("empty"), Config::default()).unwrap();
+    app.pick = Some(Separator::Nul);
+    assert!(app.print_marked().is_none());
+    assert_eq!(app.status, Some(Status::Info("Nothing to print".into())));
+
+    app.jump(&*root).unwrap();
+    app.select_path(&root.join("a.txt")).unwrap();
+    let Some(Action::PrintMany(paths, Separator::Nul)) = app.print_marked() else {
+      panic!("The selected path is not printed");
+    };
+    assert_eq!(paths, [root.join("a.txt")]);
+  }
+
+  #[test]
+  fn test_expand_command() {
+    let root = TempDir::new("expand_command");
+    fs::write(root.join("it's.txt"), "").unwrap();
+
+    let app = App::with_wd(root.to_path_buf(), Config::default()).unwrap();
+    let file = format!("{}/it'\\''s.txt", root.display());
+    assert_eq!(app.expand_command("cat %s"), format!("cat '{file}'"));
+    assert_eq!(app.expand_command("ls %d | grep 100%%"), format!("ls '{}' | grep 100%", root.display()));
+    assert_eq!(app.expand_command("echo %x %"), "echo %x %");
+    assert_eq!(app.command_dir(), &*root);
+  }
+
   #[test]
   fn test_generate_parent_path_root() {
     let path = PathBuf::from("/");
@@ -0,0 +1,314 @@
+use std::{
+  fs, io,
+  path::{Path, PathBuf},
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::error::FileSystemError;
+
+/// Paths copied or cut, to be pasted into the working directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Clipboard {
+  pub paths: Vec<PathBuf>,
+  /// Moved instead of copied when pasted
+  pub cut: bool,
+}
+
+fn display(path: &Path) -> String {
+  path.to_string_lossy().into()
+}
+
+pub fn exists(path: &Path) -> bool {
+  // Broken symlinks exist as well
+  fs::symlink_metadata(path).is_ok()
+}
+
+/// `name`, then `name` with `_1`, `_2`... added to the stem
+fn candidate_names(name: &str) -> impl Iterator<Item = String> {
+  let path = Path::new(name);
+  let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+  let extension = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+  std::iter::once(name.to_string()).chain((1..).map(move |i| format!("{stem}_{i}{extension}")))
+}
+
+/// `name` in `dir`, or with `_1`, `_2`... added to the stem if it is taken
+pub fn unique_path(dir: &Path, name: &str) -> PathBuf {
+  candidate_names(name).map(|name| dir.join(name)).find(|path| !exists(path)).expect("names are endless")
+}
+
+/// A single file name, not a path
+fn validate_name(name: &str) -> Result<(), FileSystemError> {
+  if name.is_empty() || name == "." || name == ".." || name.contains(['/', std::path::MAIN_SEPARATOR]) {
+    return Err(FileSystemError::InvalidPath(name.into()));
+  }
+  Ok(())
+}
+
+/// Copying or moving a directory into itself would never end
+fn validate_destination(path: &Path, dir: &Path) -> Result<(), FileSystemError> {
+  if dir.starts_with(path) {
+    return Err(FileSystemError::InvalidPath(format!("{} is inside {}", dir.display(), path.display())));
+  }
+  Ok(())
+}
+
+fn copy_recursive(src: &Path, dest: &Path) -> io::Result<()> {
+  let metadata = fs::symlink_metadata(src)?;
+  if metadata.is_symlink() {
+    copy_symlink(src, dest)
+  } else if metadata.is_dir() {
+    fs::create_dir(dest)?;
+    for entry in fs::read_dir(src)? {
+      let entry = entry?;
+      copy_recursive(&entry.path(), &dest.join(entry.file_name()))?;
+    }
+    Ok(())
+  } else {
+    fs::copy(src, dest).map(|_| ())
+  }
+}
+
+#[cfg(unix)]
+fn copy_symlink(src: &Path, dest: &Path) -> io::Result<()> {
+  std::os::unix::fs::symlink(fs::read_link(src)?, dest)
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(src: &Path, dest: &Path) -> io::Result<()> {
+  fs::copy(src, dest).map(|_| ())
+}
+
+/// Copy `path` into `dir`, returns the new path
+pub fn copy_into(path: &Path, dir: &Path) -> Result<PathBuf, FileSystemError> {
+  validate_destination(path, dir)?;
+  let name = path.file_name().ok_or_else(|| FileSystemError::InvalidPath(display(path)))?;
+  let dest = unique_path(dir, &name.to_string_lossy());
+  copy_recursive(path, &dest).map_err(|e| FileSystemError::CopyFailed(display(path), e))?;
+  Ok(dest)
+}
+
+/// Rename, or copy and remove across file systems
+fn move_path(path: &Path, dest: &Path) -> Result<(), FileSystemError> {
+  match fs::rename(path, dest) {
+    Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+      copy_recursive(path, dest).map_err(|e| FileSystemError::CopyFailed(display(path), e))?;
+      remove(path)
+    }
+    result => result.map_err(|e| FileSystemError::MoveFailed(display(path), e)),
+  }
+}
+
+/// Move `path` into `dir`, returns the new path
+pub fn move_into(path: &Path, dir: &Path) -> Result<PathBuf, FileSystemError> {
+  validate_destination(path, dir)?;
+  let name = path.file_name().ok_or_else(|| FileSystemError::InvalidPath(display(path)))?;
+  let dest = unique_path(dir, &name.to_string_lossy());
+  move_path(path, &dest)?;
+  Ok(dest)
+}
+
+/// Move `path` to `dest`, which must not exist
+pub fn move_to(path: &Path, dest: &Path) -> Result<(), FileSystemError> {
+  if !exists(path) {
+    return Err(FileSystemError::PathNotFound(display(path)));
+  }
+  if exists(dest) {
+    return Err(FileSystemError::AlreadyExists(display(dest)));
+  }
+  move_path(path, dest)
+}
+
+/// Rename `path` to `name` in the same directory, returns the new path
+pub fn rename(path: &Path, name: &str) -> Result<PathBuf, FileSystemError> {
+  validate_name(name)?;
+  let dest = path.with_file_name(name);
+  if dest == path {
+    return Ok(dest);
+  }
+  if exists(&dest) {
+    return Err(FileSystemError::AlreadyExists(display(&dest)));
+  }
+  fs::rename(path, &dest).map_err(|e| FileSystemError::MoveFailed(display(path), e))?;
+  Ok(dest)
+}
+
+pub fn create_dir(dir: &Path, name: &str) -> Result<PathBuf, FileSystemError> {
+  validate_name(name)?;
+  let path = dir.join(name);
+  if exists(&path) {
+    return Err(FileSystemError::AlreadyExists(display(&path)));
+  }
+  fs::create_dir(&path).map_err(|e| FileSystemError::CreationFailed(display(&path), e))?;
+  Ok(path)
+}
+
+pub fn create_file(dir: &Path, name: &str) -> Result<PathBuf, FileSystemError> {
+  validate_name(name)?;
+  let path = dir.join(name);
+  fs::OpenOptions::new().write(true).create_new(true).open(&path).map_err(|e| match e.kind() {
+    io::ErrorKind::AlreadyExists => FileSystemError::AlreadyExists(display(&path)),
+    _ => FileSystemError::CreationFailed(display(&path), e),
+  })?;
+  Ok(path)
+}
+
+/// Remove permanently, symlinks to directories are removed as links
+pub fn remove(path: &Path) -> Result<(), FileSystemError> {
+  let result = match fs::symlink_metadata(path) {
+    Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path),
+    Ok(_) => fs::remove_file(path),
+    Err(e) => Err(e),
+  };
+  result.map_err(|e| FileSystemError::RemoveFailed(display(path), e))
+}
+
+/// `XDG_DATA_HOME/Trash`, or `HOME/.local/share/Trash`
+pub fn trash_dir() -> Result<PathBuf, FileSystemError> {
+  let data_dir = match std::env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+    Some(dir) => PathBuf::from(dir),
+    None => {
+      home::home_dir().ok_or_else(|| FileSystemError::PathNotFound("home directory".into()))?.join(".local/share")
+    }
+  };
+  Ok(data_dir.join("Trash"))
+}
+
+/// Move `path` to the trash as the FreeDesktop.org trash specification, returns the path in the trash
+pub fn trash(path: &Path, trash_dir: &Path) -> Result<PathBuf, FileSystemError> {
+  let (files, info) = (trash_dir.join("files"), trash_dir.join("info"));
+  for dir in [&files, &info] {
+    fs::create_dir_all(dir).map_err(|e| FileSystemError::CreationFailed(display(dir), e))?;
+  }
+  let path = std::path::absolute(path).map_err(|_| FileSystemError::InvalidPath(display(path)))?;
+  let name = path.file_name().ok_or_else(|| FileSystemError::InvalidPath(display(&path)))?.to_string_lossy();
+
+  // The info file is created first to reserve the name in the trash
+  let mut names = candidate_names(&name);
+  let (trashed, info_path) = loop {
+    let name = names.next().expect("names are endless");
+    let (trashed, info_path) = (files.join(&name), info.join(format!("{name}.trashinfo")));
+    if exists(&trashed) {
+      continue;
+    }
+    match fs::OpenOptions::new().write(true).create_new(true).open(&info_path) {
+      Ok(_) => break (trashed, info_path),
+      Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+      Err(e) => return Err(FileSystemError::CreationFailed(display(&info_path), e)),
+    }
+  };
+  let content =
+    format!("[Trash Info]\nPath={}\nDeletionDate={}\n", percent_encode(&path), utc_date_time(SystemTime::now()));
+  let result =
+    fs::write(&info_path, content).map_err(FileSystemError::FileWriteFailed).and_then(|_| move_path(&path, &trashed));
+  if let Err(e) = result {
+    let _ = fs::remove_file(&info_path);
+    return Err(e);
+  }
+  Ok(trashed)
+}
+
+/// Bytes other than unreserved characters and `/` are escaped as `%XX`
+fn percent_encode(path: &Path) -> String {
+  path
+    .as_os_str()
+    .as_encoded_bytes()
+    .iter()
+    .map(|b| match b {
+      b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => (*b as char).to_string(),
+      _ => format!("%{b:02X}"),
+    })
+    .collect()
+}
+
+/// `YYYY-MM-DDThh:mm:ss` in UTC
+pub fn utc_date_time(time: SystemTime) -> String {
+  let secs = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+  let (days, secs) = ((secs / 86400) as i64, secs % 86400);
+  // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+  let z = days + 719468;
+  let era = z.div_euclid(146097);
+  let doe = z.rem_euclid(146097);
+  let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = yoe + era * 400 + i64::from(month <= 2);
+  format!("{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}", secs / 3600, secs % 3600 / 60, secs % 60)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::TempDir;
+  use std::time::Duration;
+
+  #[test]
+  fn test_unique_path() {
+    let dir = TempDir::new("fileop_unique");
+    assert_eq!(unique_path(&dir, "a.txt"), dir.join("a.txt"));
+    fs::write(dir.join("a.txt"), "").unwrap();
+    fs::write(dir.join("a_1.txt"), "").unwrap();
+    assert_eq!(unique_path(&dir, "a.txt"), dir.join("a_2.txt"));
+    fs::create_dir(dir.join("b")).unwrap();
+    assert_eq!(unique_path(&dir, "b"), dir.join("b_1"));
+  }
+
+  #[test]
+  fn test_copy_and_move() {
+    let dir = TempDir::new("fileop_copy");
+    fs::create_dir_all(dir.join("src/sub")).unwrap();
+    fs::write(dir.join("src/sub/file"), "content").unwrap();
+    fs::create_dir(dir.join("dest")).unwrap();
+
+    let copied = copy_into(&dir.join("src"), &dir.join("dest")).unwrap();
+    assert_eq!(copied, dir.join("dest/src"));
+    assert_eq!(fs::read_to_string(dir.join("dest/src/sub/file")).unwrap(), "content");
+    // Copied next to itself
+    assert_eq!(copy_into(&dir.join("src"), &dir).unwrap(), dir.join("src_1"));
+    assert!(copy_into(&dir.join("src"), &dir.join("src/sub")).is_err());
+
+    let moved = move_into(&dir.join("src_1"), &dir.join("dest")).unwrap();
+    assert_eq!(moved, dir.join("dest/src_1"));
+    assert!(!dir.join("src_1").exists());
+  }
+
+  #[test]
+  fn test_rename_and_create() {
+    let dir = TempDir::new("fileop_rename");
+    let file = create_file(&dir, "a.txt").unwrap();
+    assert!(matches!(create_file(&dir, "a.txt"), Err(FileSystemError::AlreadyExists(_))));
+    create_dir(&dir, "b").unwrap();
+    assert!(matches!(create_dir(&dir, "b"), Err(FileSystemError::AlreadyExists(_))));
+    assert!(matches!(create_dir(&dir, "c/d"), Err(FileSystemError::InvalidPath(_))));
+
+    assert_eq!(rename(&file, "c.txt").unwrap(), dir.join("c.txt"));
+    assert!(matches!(rename(&dir.join("c.txt"), "b"), Err(FileSystemError::AlreadyExists(_))));
+    assert!(matches!(rename(&dir.join("c.txt"), ""), Err(FileSystemError::InvalidPath(_))));
+
+    remove(&dir.join("b")).unwrap();
+    assert!(!dir.join("b").exists());
+  }
+
+  #[test]
+  fn test_trash() {
+    let dir = TempDir::new("fileop_trash");
+    let trash_dir = dir.join("Trash");
+    for _ in 0..2 {
+      fs::write(dir.join("a b.txt"), "").unwrap();
+      trash(&dir.join("a b.txt"), &trash_dir).unwrap();
+    }
+    assert!(!dir.join("a b.txt").exists());
+    assert!(trash_dir.join("files/a b.txt").exists());
+    assert!(trash_dir.join("files/a b_1.txt").exists());
+    let info = fs::read_to_string(trash_dir.join("info/a b.txt.trashinfo")).unwrap();
+    assert!(info.starts_with(&format!("[Trash Info]\nPath={}/a%20b.txt\nDeletionDate=", percent_encode(&dir))));
+    assert!(trash_dir.join("info/a b_1.txt.trashinfo").exists());
+  }
+
+  #[test]
+  fn test_utc_date_time() {
+    assert_eq!(utc_date_time(UNIX_EPOCH), "1970-01-01T00:00:00");
+    assert_eq!(utc_date_time(UNIX_EPOCH + Duration::from_secs(951_782_400 + 3661)), "2000-02-29T01:01:01");
+  }
+}
@@ -0,0 +1,147 @@
+use std::cell::RefCell;
+
+const SCORE_MATCH: i64 = 16;
+const BONUS_BOUNDARY: i64 = 8;
+const BONUS_CONSECUTIVE: i64 = 8;
+const PENALTY_GAP_START: i64 = 3;
+const PENALTY_GAP_EXTENSION: i64 = 1;
+
+thread_local! {
+  /// The score table of `fuzzy_match`, reused instead of allocated for every candidate
+  static SCORES: RefCell<Vec<Option<i64>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Match and score positions in `text`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzyMatch {
+  pub score: i64,
+  /// Char indices of `text` matched with `pattern`
+  pub positions: Vec<usize>,
+}
+
+/// Smart-case: case-sensitive only when the pattern has an uppercase letter.
+/// Shared by every search, so that they agree on it.
+pub(crate) fn is_case_sensitive(pattern: &str) -> bool {
+  pattern.chars().any(|c| c.is_uppercase())
+}
+
+fn bonus(text: &[char], j: usize) -> i64 {
+  if j == 0 {
+    return BONUS_BOUNDARY;
+  }
+  let (prev, c) = (text[j - 1], text[j]);
+  if !prev.is_alphanumeric() || (prev.is_lowercase() && c.is_uppercase()) { BONUS_BOUNDARY } else { 0 }
+}
+
+/// `pattern` has to appear in `text` as a subsequence.
+/// Matches at word boundaries and consecutive matches score higher, gaps between them are penalized.
+pub fn fuzzy_match(text: &str, pattern: &str) -> Option<FuzzyMatch> {
+  let case_sensitive = is_case_sensitive(pattern);
+  let normalize = |c: char| if case_sensitive { c } else { c.to_lowercase().next().unwrap_or(c) };
+  let text = text.chars().collect::<Vec<_>>();
+  let folded = text.iter().map(|c| normalize(*c)).collect::<Vec<_>>();
+  let pattern = pattern.chars().map(normalize).collect::<Vec<_>>();
+  if pattern.is_empty() {
+    return Some(FuzzyMatch { score: 0, positions: vec![] });
+  }
+  if pattern.len() > text.len() {
+    return None;
+  }
+
+  let (m, n) = (pattern.len(), text.len());
+  SCORES.with_borrow_mut(|scores| {
+    // scores[i * n + j]: best score of `pattern[..=i]` with `pattern[i]` matched at `text[j]`
+    scores.clear();
+    scores.resize(m * n, None);
+    let at = |i: usize, j: usize| i * n + j;
+    for i in 0..m {
+      // Best score before `j - 1` with the gap penalty up to `j`
+      let mut gap: Option<i64> = None;
+      for j in i..n {
+        if i > 0 && j >= 2 {
+          let opened = scores[at(i - 1, j - 2)].map(|s: i64| s - PENALTY_GAP_START);
+          gap = gap.map(|g| g - PENALTY_GAP_EXTENSION).max(opened);
+        }
+        if folded[j] != pattern[i] {
+          continue;
+        }
+        let previous = if i == 0 {
+          Some(0)
+        } else {
+          let consecutive = scores[at(i - 1, j - 1)].map(|s| s + BONUS_CONSECUTIVE);
+          consecutive.max(gap)
+        };
+        scores[at(i, j)] = previous.map(|s| s + SCORE_MATCH + bonus(&text, j));
+      }
+    }
+
+    let (mut j, score) =
+      scores[at(m - 1, 0)..].iter().enumerate().filter_map(|(j, s)| s.map(|s| (j, s))).max_by_key(|(_, s)| *s)?;
+
+    // Trace back the positions
+    let mut positions = vec![j];
+    let mut current = score;
+    for i in (1..m).rev() {
+      let previous = current - SCORE_MATCH - bonus(&text, j);
+      let k = if scores[at(i - 1, j - 1)].is_some_and(|s| s + BONUS_CONSECUTIVE == previous) {
+        j - 1
+      } else {
+        (0..j - 1)
+          .rev()
+          .find(|k| {
+            scores[at(i - 1, *k)]
+              .is_some_and(|s| s - PENALTY_GAP_START - PENALTY_GAP_EXTENSION * (j - k - 2) as i64 == previous)
+          })
+          .expect("a gap leads to the score")
+      };
+      current = scores[at(i - 1, k)].expect("the position is matched");
+      j = k;
+      positions.push(j);
+    }
+    positions.reverse();
+
+    Some(FuzzyMatch { score, positions })
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_fuzzy_match() {
+    assert_eq!(fuzzy_match("src", "sc").map(|m| m.positions), Some(vec![0, 2]));
+    assert_eq!(fuzzy_match("src", "cs"), None);
+    assert_eq!(fuzzy_match("src", "srcs"), None);
+    assert_eq!(fuzzy_match("src", "").map(|m| m.score), Some(0));
+  }
+
+  #[test]
+  fn test_smart_case() {
+    assert!(fuzzy_match("Cargo.toml", "cargo").is_some());
+    assert!(fuzzy_match("Cargo.toml", "Cargo").is_some());
+    assert!(fuzzy_match("cargo.toml", "Cargo").is_none());
+  }
+
+  #[test]
+  fn test_prefer_consecutive_and_boundary() {
+    // `main` is matched as a word rather than scattered
+    assert_eq!(fuzzy_match("my_awesome_main.rs", "main").map(|m| m.positions), Some(vec![11, 12, 13, 14]));
+    let consecutive = fuzzy_match("foobar", "bar").unwrap().score;
+    let scattered = fuzzy_match("bxaxr", "bar").unwrap().score;
+    assert!(consecutive > scattered);
+    let boundary = fuzzy_match("foo_bar", "b").unwrap().score;
+    let middle = fuzzy_match("foobar", "b").unwrap().score;
+    assert!(boundary > middle);
+    let camel = fuzzy_match("FooBar", "fb").unwrap().score;
+    let plain = fuzzy_match("foobar", "fb").unwrap().score;
+    assert!(camel > plain);
+  }
+
+  #[test]
+  fn test_gap_penalty() {
+    let short = fuzzy_match("axxb", "ab").unwrap().score;
+    let long = fuzzy_match("axxxxxxb", "ab").unwrap().score;
+    assert!(short > long);
+  }
+}
@@ -0,0 +1,117 @@
+use std::{
+  fs,
+  ops::Range,
+  path::{Path, PathBuf},
+};
+
+use super::fuzzy::is_case_sensitive;
+use super::item::decode;
+use super::walk::{Walk, WalkOptions, descendants};
+
+/// Grepping stops after finding this many lines
+const MAX_MATCHES: usize = 10_000;
+
+/// A line of a file which contains the pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrepMatch {
+  pub path: PathBuf,
+  /// Starts from `0`
+  pub line: usize,
+  pub text: String,
+}
+
+/// Lines containing `pattern` in the files below `root`
+#[derive(Debug)]
+pub struct Grep {
+  pub pattern: String,
+  pub walk: Walk<GrepMatch>,
+}
+
+impl Grep {
+  /// Files larger than `max_size` bytes are skipped
+  pub fn start(root: PathBuf, pattern: &str, options: WalkOptions, max_size: u64) -> Self {
+    let walk = Walk::spawn(root, {
+      let pattern = pattern.to_string();
+      move |root, batcher, cancel| {
+        if pattern.is_empty() {
+          return;
+        }
+        let mut found = 0;
+        descendants(root, &options, cancel, |entry| {
+          // Only regular files, not symlinks
+          if !entry.file_type().is_ok_and(|t| t.is_file()) || entry.metadata().is_ok_and(|m| m.len() > max_size) {
+            return true;
+          }
+          let Some(s) = read_text(&entry.path()) else {
+            return true;
+          };
+          for (line, text) in s.lines().enumerate() {
+            if find(text, &pattern).is_none() {
+              continue;
+            }
+            found += 1;
+            let m = GrepMatch { path: entry.path(), line, text: text.to_string() };
+            if !batcher.push(m) || found >= MAX_MATCHES {
+              return false;
+            }
+          }
+          true
+        });
+      }
+    });
+    Grep { pattern: pattern.into(), walk }
+  }
+}
+
+/// Decoded as the preview does, `None` if the file is binary
+fn read_text(path: &Path) -> Option<String> {
+  decode(&fs::read(path).ok()?)
+}
+
+/// Char range of the first `pattern` in `text`, in smart-case
+pub fn find(text: &str, pattern: &str) -> Option<Range<usize>> {
+  let case_sensitive = is_case_sensitive(pattern);
+  let normalize = |c: char| if case_sensitive { c } else { c.to_lowercase().next().unwrap_or(c) };
+  let text = text.chars().map(normalize).collect::<Vec<_>>();
+  let pattern = pattern.chars().map(normalize).collect::<Vec<_>>();
+  if pattern.is_empty() {
+    return None;
+  }
+  let start = text.windows(pattern.len()).position(|window| window == pattern.as_slice())?;
+  Some(start..start + pattern.len())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::TempDir;
+
+  #[test]
+  fn test_find() {
+    assert_eq!(find("fn main() {}", "main"), Some(3..7));
+    assert_eq!(find("fn Main() {}", "main"), Some(3..7));
+    assert_eq!(find("fn main() {}", "Main"), None);
+    assert_eq!(find("ことばのmain", "main"), Some(4..8));
+    assert_eq!(find("main", ""), None);
+  }
+
+  #[test]
+  fn test_grep() {
+    let root = TempDir::new("grep");
+    fs::create_dir_all(root.join("a/.git")).unwrap();
+    fs::write(root.join("a/lib.rs"), "use std::fs;\n\nfn todo() {}\n// TODO: more\n").unwrap();
+    fs::write(root.join("a/.git/HEAD"), "todo").unwrap();
+    fs::write(root.join("binary"), b"todo\0").unwrap();
+    fs::write(root.join("large.txt"), format!("todo\n{}", "x".repeat(100))).unwrap();
+    fs::write(root.join("sjis.txt"), encoding_rs::SHIFT_JIS.encode("todo: 日本語\n").0).unwrap();
+
+    let options = WalkOptions { max_depth: 8, show_hidden: true, ignore: vec![".git".into()] };
+    let mut grep = Grep::start(root.to_path_buf(), "todo", options, 64);
+    grep.walk.wait();
+    let mut found = grep.walk.found.iter().map(|m| (grep.walk.relative(&m.path), m.line)).collect::<Vec<_>>();
+    found.sort();
+    assert_eq!(found, [(Path::new("a/lib.rs"), 2), (Path::new("a/lib.rs"), 3), (Path::new("sjis.txt"), 0)]);
+    assert!(grep.walk.found.iter().any(|m| m.text == "// TODO: more"));
+    assert!(grep.walk.found.iter().any(|m| m.text == "todo: 日本語"));
+  }
+}
@@ -0,0 +1,195 @@
+use std::{
+  path::{Path, PathBuf},
+  sync::OnceLock,
+};
+
+use ratatui::{
+  style::{Color, Modifier, Style},
+  text::Span,
+};
+use syntect::{
+  highlighting::{self, FontStyle, HighlightIterator, HighlightState, Theme},
+  parsing::{ParseState, ScopeStack, SyntaxReference, SyntaxSet},
+};
+use two_face::theme::EmbeddedThemeName;
+
+use super::{Item, ItemInfo};
+
+/// Lines longer than this, such as minified code, take too long to parse while the keys wait for the drawing,
+/// so the highlighting stops at them
+const MAX_LINE_LEN: usize = 4096;
+
+/// Loaded on the first preview, which takes a moment
+fn syntaxes() -> &'static SyntaxSet {
+  static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+  SYNTAXES.get_or_init(two_face::syntax::extra_newlines)
+}
+
+fn theme() -> &'static Theme {
+  static THEME: OnceLock<Theme> = OnceLock::new();
+  THEME.get_or_init(|| two_face::theme::extra().get(EmbeddedThemeName::Base16OceanDark).clone())
+}
+
+/// Syntax by the extension or the file name, then by the shebang or the modeline
+fn find_syntax(path: &Path, first_line: &str) -> Option<&'static SyntaxReference> {
+  let syntaxes = syntaxes();
+  let by_name =
+    |name: Option<&std::ffi::OsStr>| name.and_then(|name| syntaxes.find_syntax_by_extension(&name.to_string_lossy()));
+  by_name(path.extension())
+    .or_else(|| by_name(path.file_name()))
+    .or_else(|| syntaxes.find_syntax_by_first_line(first_line))
+}
+
+fn to_style(style: highlighting::Style) -> Style {
+  let mut modifier = Modifier::empty();
+  if style.font_style.contains(FontStyle::BOLD) {
+    modifier |= Modifier::BOLD;
+  }
+  if style.font_style.contains(FontStyle::ITALIC) {
+    modifier |= Modifier::ITALIC;
+  }
+  if style.font_style.contains(FontStyle::UNDERLINE) {
+    modifier |= Modifier::UNDERLINED;
+  }
+  Style::default().fg(Color::Rgb(style.foreground.r, style.foreground.g, style.foreground.b)).add_modifier(modifier)
+}
+
+/// Lines of a file highlighted so far, continued from the states where they stop
+#[derive(Debug)]
+struct Highlighted {
+  path: PathBuf,
+  /// `None` if the file has no known syntax
+  states: Option<(ParseState, HighlightState)>,
+  lines: Vec<Vec<Span<'static>>>,
+}
+
+/// Syntax highlighting of the file contents shown in the preview or the working block.
+/// Lines are highlighted only as far as they are shown, and kept until `clear` when the contents are read again.
+#[derive(Debug, Default)]
+pub struct Highlighter {
+  file: Option<Highlighted>,
+}
+
+impl Highlighter {
+  /// Highlight the lines of `contents` before `end`, which are the lines of the file `path`
+  pub fn highlight(&mut self, path: &Path, contents: &[ItemInfo], end: usize) {
+    if self.file.as_ref().is_none_or(|file| file.path != path) {
+      let first_line = match contents.first().map(|item| &item.item) {
+        Some(Item::Content(line)) => line.as_str(),
+        _ => "",
+      };
+      let states = find_syntax(path, first_line).map(|syntax| {
+        let highlighter = highlighting::Highlighter::new(theme());
+        (ParseState::new(syntax), HighlightState::new(&highlighter, ScopeStack::new()))
+      });
+      self.file = Some(Highlighted { path: path.into(), states, lines: vec![] });
+    }
+
+    let Some(file) = self.file.as_mut() else {
+      return;
+    };
+    let Some((parse, highlight)) = file.states.as_mut() else {
+      return;
+    };
+    let highlighter = highlighting::Highlighter::new(theme());
+    let mut stopped = false;
+    for item in contents.iter().take(end).skip(file.lines.len()) {
+      let Item::Content(text) = &item.item else {
+        break;
+      };
+      if text.len() > MAX_LINE_LEN {
+        stopped = true;
+        break;
+      }
+      // The syntaxes expect the line ending
+      let line = format!("{text}\n");
+      let Ok(ops) = parse.parse_line(&line, syntaxes()) else {
+        stopped = true;
+        break;
+      };
+      let spans = HighlightIterator::new(highlight, &ops, &line, &highlighter)
+        .map(|(style, s)| Span::styled(s.trim_end_matches('\n').to_string(), to_style(style)))
+        .filter(|span| !span.content.is_empty())
+        .collect();
+      file.lines.push(spans);
+    }
+    // The rest is left plain
+    if stopped {
+      file.states = None;
+    }
+  }
+
+  /// Forget the lines highlighted so far, after the file is read again
+  pub fn clear(&mut self) {
+    self.file = None;
+  }
+
+  /// Highlighted lines of `path` from the first line, fewer than the file if the rest is not shown yet
+  pub fn lines(&self, path: &Path) -> &[Vec<Span<'static>>] {
+    match &self.file {
+      Some(file) if file.path == path => &file.lines,
+      _ => &[],
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn contents(text: &str) -> Vec<ItemInfo> {
+    text.lines().enumerate().map(|(i, line)| ItemInfo { item: Item::Content(line.into()), index: Some(i) }).collect()
+  }
+
+  #[test]
+  fn test_find_syntax() {
+    let name =
+      |path: &str, first_line: &str| find_syntax(Path::new(path), first_line).map(|syntax| syntax.name.as_str());
+    assert_eq!(name("/tmp/main.rs", ""), Some("Rust"));
+    assert_eq!(name("/tmp/Cargo.toml", ""), Some("TOML"));
+    assert_eq!(name("/tmp/run", "#!/usr/bin/env python3"), Some("Python"));
+    assert_eq!(name("/tmp/notes", "just text"), None);
+  }
+
+  #[test]
+  fn test_highlight() {
+    let contents = contents("fn main() {\n  let s = \"a\";\n}\n");
+    let path = Path::new("/tmp/ed_test_highlight.rs");
+    let mut highlighter = Highlighter::default();
+    highlighter.highlight(path, &contents, 2);
+    let lines = highlighter.lines(path);
+    assert_eq!(lines.len(), 2);
+    // The text is kept as it is, in more than one color
+    assert_eq!(lines[1].iter().map(|span| span.content.as_ref()).collect::<String>(), "  let s = \"a\";");
+    assert!(lines[1].iter().any(|span| span.style != lines[1][0].style));
+
+    highlighter.highlight(path, &contents, 10);
+    assert_eq!(highlighter.lines(path).len(), 3);
+    assert!(highlighter.lines(Path::new("/tmp/other.rs")).is_empty());
+
+    // Highlighted again from the start after `clear`
+    let edited = self::contents("fn edited() {}\n");
+    highlighter.highlight(path, &edited, 10);
+    assert_eq!(highlighter.lines(path).len(), 3);
+    highlighter.clear();
+    highlighter.highlight(path, &edited, 10);
+    assert_eq!(
+      highlighter.lines(path)[0].iter().map(|span| span.content.as_ref()).collect::<String>(),
+      "fn edited() {}"
+    );
+
+    highlighter.highlight(Path::new("/tmp/notes"), &contents, 10);
+    assert!(highlighter.lines(Path::new("/tmp/notes")).is_empty());
+  }
+
+  #[test]
+  fn test_highlight_long_line() {
+    let minified = format!("let a = 1;\n{}\nlet b = 2;\n", "a = a + 1; ".repeat(1000));
+    let path = Path::new("/tmp/ed_test_minified.js");
+    let mut highlighter = Highlighter::default();
+    highlighter.highlight(path, &contents(&minified), 10);
+    assert_eq!(highlighter.lines(path).len(), 1);
+    highlighter.highlight(path, &contents(&minified), 10);
+    assert_eq!(highlighter.lines(path).len(), 1);
+  }
+}
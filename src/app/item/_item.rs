@@ -1,9 +1,8 @@
-use std::{
-  fs,
-  path::{Path, PathBuf},
-};
+use std::path::{Path, PathBuf};
 
-use super::App;
+use super::preview::read_preview;
+use super::{App, ReadOptions};
+use crate::app::GrepMatch;
 use crate::error::FileSystemError;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -11,6 +10,9 @@ pub enum Item {
   Path(ItemPath),
   Content(String),
   Search(String),
+  Grep(GrepMatch),
+  /// A line about the preview itself, such as the truncated marker or the hexdump of a binary file
+  Note(String),
   None,
 }
 
@@ -57,6 +59,21 @@ impl ItemSymlink {
 }
 
 impl ItemPath {
+  /// Classify by the file type on disk
+  pub fn from_path(path: PathBuf) -> Self {
+    if path.is_file() && path.is_symlink() {
+      ItemPath::Symlink(ItemSymlink::File(path))
+    } else if path.is_dir() && path.is_symlink() {
+      ItemPath::Symlink(ItemSymlink::Dir(path))
+    } else if path.is_file() {
+      ItemPath::File(path)
+    } else if path.is_dir() {
+      ItemPath::Dir(path)
+    } else {
+      ItemPath::Unknown(path)
+    }
+  }
+
   fn get_path(&self) -> PathBuf {
     match self {
       ItemPath::Dir(path) => path,
@@ -98,24 +115,20 @@ impl ItemInfo {
   pub fn default() -> Self {
     Self { item: Item::new(), index: None }
   }
-  pub fn generate_child_items(&self) -> anyhow::Result<Vec<Self>> {
-    if self.is_symlink() {
-      if let Item::Path(path) = &self.item {
-        return App::make_items(path.read_link()?);
-      }
+  pub fn generate_child_items(&self, options: ReadOptions) -> anyhow::Result<Vec<Self>> {
+    if self.is_symlink()
+      && let Item::Path(path) = &self.item
+    {
+      return App::make_items(path.read_link()?, options);
     }
     Ok(if self.is_dir() {
       let path =
         self.get_path().ok_or_else(|| FileSystemError::InvalidPath("Directory item has no valid path".to_string()))?;
-      App::make_items(path)?
-    } else if self.is_file() && self.can_read() {
+      App::make_items(path, options)?
+    } else if (self.is_file() && self.can_read()) || matches!(self.item, Item::Grep(_)) {
       let path =
         self.get_path().ok_or_else(|| FileSystemError::InvalidPath("File item has no valid path".to_string()))?;
-      if let Ok(s) = fs::read_to_string(&path) {
-        s.lines().enumerate().map(|(i, s)| Self { item: Item::Content(s.to_string()), index: Some(i) }).collect()
-      } else {
-        vec![Self::default()]
-      }
+      read_preview(&path).unwrap_or_else(|_| vec![Self::default()])
     } else {
       vec![Self::default()]
     })
@@ -136,7 +149,11 @@ impl ItemInfo {
     if let Some(p) = self.get_path() { p.is_symlink() } else { false }
   }
   pub fn get_path(&self) -> Option<PathBuf> {
-    if let Item::Path(path) = &self.item { Some(path.get_path()) } else { None }
+    match &self.item {
+      Item::Path(path) => Some(path.get_path()),
+      Item::Grep(m) => Some(m.path.clone()),
+      _ => None,
+    }
   }
 }
 
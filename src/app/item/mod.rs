@@ -1,7 +1,11 @@
 mod _item;
+mod preview;
 mod read;
+mod sort;
 
 pub use self::_item::{Item, ItemInfo, ItemPath, ItemSymlink};
-pub use self::read::read_items;
+pub use self::preview::decode;
+pub use self::read::{ReadOptions, is_hidden, read_items};
+pub use self::sort::SortMode;
 
 pub use super::App;
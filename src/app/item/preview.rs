@@ -0,0 +1,210 @@
+use std::{
+  fs::File,
+  io::{self, Read},
+  path::Path,
+};
+
+use encoding_rs::{EUC_JP, Encoding, SHIFT_JIS};
+
+use super::{Item, ItemInfo};
+
+/// Bytes of a file read for the preview at most, so that a large file does not freeze the UI
+const PREVIEW_MAX_BYTES: u64 = 256 * 1024;
+/// Lines of a file shown at most
+const PREVIEW_MAX_LINES: usize = 5000;
+/// Bytes of a binary file shown in the hexdump
+const HEXDUMP_BYTES: usize = 256;
+/// Bytes searched for NUL to tell a binary file
+const BINARY_CHECK_BYTES: usize = 8 * 1024;
+
+/// Lines of the head of a text file, followed by a note if it is cut.
+/// A binary file is shown as its size, type and a hexdump of the head.
+pub fn read_preview(path: &Path) -> io::Result<Vec<ItemInfo>> {
+  let file = File::open(path)?;
+  let size = file.metadata()?.len();
+  let mut bytes = vec![];
+  file.take(PREVIEW_MAX_BYTES).read_to_end(&mut bytes)?;
+  Ok(preview(&bytes, size))
+}
+
+fn preview(bytes: &[u8], size: u64) -> Vec<ItemInfo> {
+  let truncated = (bytes.len() as u64) < size;
+  // The last line may be cut in the middle of a character
+  let text_bytes = match bytes.iter().rposition(|b| *b == b'\n') {
+    Some(i) if truncated => &bytes[..=i],
+    None if truncated => complete_utf8(bytes),
+    _ => bytes,
+  };
+  let Some(text) = decode(text_bytes) else {
+    return binary_summary(bytes, size);
+  };
+
+  let mut lines = text.lines();
+  let mut items = lines
+    .by_ref()
+    .take(PREVIEW_MAX_LINES)
+    .enumerate()
+    .map(|(i, line)| ItemInfo { item: Item::Content(line.into()), index: Some(i) })
+    .collect::<Vec<_>>();
+  if truncated || lines.next().is_some() {
+    let note = format!("--- truncated, {} lines of {} shown ---", items.len(), format_size(size));
+    items.push(ItemInfo { item: Item::Note(note), index: None });
+  }
+  items
+}
+
+/// `bytes` up to the last complete character, if they are UTF-8 cut in the middle of one
+fn complete_utf8(bytes: &[u8]) -> &[u8] {
+  match std::str::from_utf8(bytes) {
+    Err(e) if e.error_len().is_none() => &bytes[..e.valid_up_to()],
+    _ => bytes,
+  }
+}
+
+/// UTF-8, or UTF-16 with a BOM, Shift_JIS or EUC-JP if it is not valid UTF-8.
+/// `None` for a binary file.
+pub fn decode(bytes: &[u8]) -> Option<String> {
+  if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
+    return encoding.decode_without_bom_handling_and_without_replacement(&bytes[bom_len..]).map(|s| s.into_owned());
+  }
+  if bytes[..bytes.len().min(BINARY_CHECK_BYTES)].contains(&0) {
+    return None;
+  }
+  if let Ok(text) = std::str::from_utf8(bytes) {
+    return Some(text.into());
+  }
+  // EUC-JP first, since most EUC-JP text is also valid Shift_JIS, but not the other way around
+  [EUC_JP, SHIFT_JIS]
+    .into_iter()
+    .find_map(|encoding| encoding.decode_without_bom_handling_and_without_replacement(bytes))
+    .map(|s| s.into_owned())
+}
+
+fn binary_summary(bytes: &[u8], size: u64) -> Vec<ItemInfo> {
+  let note = |s: String| ItemInfo { item: Item::Note(s), index: None };
+  let mut items =
+    vec![note(format!("binary file, {}, {}", format_size(size), binary_type(bytes))), note(String::new())];
+  items.extend(
+    bytes[..bytes.len().min(HEXDUMP_BYTES)].chunks(16).enumerate().map(|(i, chunk)| note(hexdump_line(i * 16, chunk))),
+  );
+  items
+}
+
+/// `00000010  7f 45 4c 46 02 01 01 00  00 00 00 00 00 00 00 00  |.ELF............|`
+fn hexdump_line(offset: usize, chunk: &[u8]) -> String {
+  let mut hex = String::new();
+  for i in 0..16 {
+    if i == 8 {
+      hex.push(' ');
+    }
+    match chunk.get(i) {
+      Some(b) => hex.push_str(&format!("{b:02x} ")),
+      None => hex.push_str("   "),
+    }
+  }
+  let ascii =
+    chunk.iter().map(|b| if b.is_ascii_graphic() || *b == b' ' { *b as char } else { '.' }).collect::<String>();
+  format!("{offset:08x}  {hex} |{ascii}|")
+}
+
+/// Guessed from the magic number
+fn binary_type(bytes: &[u8]) -> &'static str {
+  const MAGICS: [(&[u8], &str); 14] = [
+    (b"\x7fELF", "ELF executable"),
+    (b"MZ", "Windows executable"),
+    (b"\xcf\xfa\xed\xfe", "Mach-O executable"),
+    (b"\0asm", "WebAssembly"),
+    (b"\x89PNG\r\n\x1a\n", "PNG image"),
+    (b"\xff\xd8\xff", "JPEG image"),
+    (b"GIF8", "GIF image"),
+    (b"%PDF", "PDF document"),
+    (b"PK\x03\x04", "Zip archive"),
+    (b"\x1f\x8b", "gzip compressed"),
+    (b"\x28\xb5\x2f\xfd", "Zstandard compressed"),
+    (b"\xfd7zXZ\0", "XZ compressed"),
+    (b"7z\xbc\xaf\x27\x1c", "7-Zip archive"),
+    (b"SQLite format 3\0", "SQLite database"),
+  ];
+  MAGICS.iter().find(|(magic, _)| bytes.starts_with(magic)).map_or("data", |(_, name)| name)
+}
+
+/// `1.5 KiB`, or the bytes if less than 1 KiB
+fn format_size(size: u64) -> String {
+  const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+  if size < 1024 {
+    return format!("{size} B");
+  }
+  let mut value = size as f64 / 1024.0;
+  let mut unit = 0;
+  while value >= 1024.0 && unit < UNITS.len() - 1 {
+    value /= 1024.0;
+    unit += 1;
+  }
+  format!("{value:.1} {}", UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn texts(items: &[ItemInfo]) -> Vec<String> {
+    items
+      .iter()
+      .map(|item| match &item.item {
+        Item::Content(s) | Item::Note(s) => s.clone(),
+        _ => panic!("not a line"),
+      })
+      .collect()
+  }
+
+  #[test]
+  fn test_text() {
+    let items = preview(b"a\nb\n", 4);
+    assert_eq!(texts(&items), ["a", "b"]);
+    assert_eq!(items[1].index, Some(1));
+  }
+
+  #[test]
+  fn test_truncated() {
+    // The line cut at the end is dropped
+    let items = preview(b"a\nb\nc", 10 * 1024 * 1024);
+    assert_eq!(texts(&items), ["a", "b", "--- truncated, 2 lines of 10.0 MiB shown ---"]);
+    assert_eq!(items[2].index, None);
+
+    let text = "line\n".repeat(PREVIEW_MAX_LINES + 1);
+    let items = preview(text.as_bytes(), text.len() as u64);
+    assert_eq!(items.len(), PREVIEW_MAX_LINES + 1);
+    assert!(matches!(&items[PREVIEW_MAX_LINES].item, Item::Note(_)));
+
+    // A single line cut in the middle of a character
+    let text = "日本語".as_bytes();
+    let items = preview(&text[..7], 1024);
+    assert_eq!(texts(&items), ["日本", "--- truncated, 1 lines of 1.0 KiB shown ---"]);
+  }
+
+  #[test]
+  fn test_japanese() {
+    let (sjis, _, _) = SHIFT_JIS.encode("日本語のテキスト\n");
+    assert_eq!(texts(&preview(&sjis, sjis.len() as u64)), ["日本語のテキスト"]);
+    let (euc, _, _) = EUC_JP.encode("日本語のテキスト\n");
+    assert_eq!(texts(&preview(&euc, euc.len() as u64)), ["日本語のテキスト"]);
+  }
+
+  #[test]
+  fn test_binary() {
+    let bytes = b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0\x03\0";
+    let lines = texts(&preview(bytes, 2048));
+    assert_eq!(lines[0], "binary file, 2.0 KiB, ELF executable");
+    assert_eq!(lines[2], "00000000  7f 45 4c 46 02 01 01 00  00 00 00 00 00 00 00 00  |.ELF............|");
+    assert_eq!(lines[3], format!("00000010  03 00 {}|..|", " ".repeat(44)));
+    // Neither UTF-8 nor Japanese
+    assert_eq!(texts(&preview(b"\xff\xff\xfd\xa0", 4))[0], "binary file, 4 B, data");
+  }
+
+  #[test]
+  fn test_format_size() {
+    assert_eq!(format_size(1023), "1023 B");
+    assert_eq!(format_size(1536), "1.5 KiB");
+    assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+  }
+}
@@ -3,26 +3,38 @@ use std::path::Path;
 
 use crate::app::{Item, ItemInfo};
 
-use super::_item::{ItemPath, ItemSymlink};
+use super::_item::ItemPath;
+use super::sort::{SortMode, sort_items};
 
-pub fn read_items<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<ItemInfo>> {
+/// How the entries of a directory are listed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadOptions {
+  pub show_hidden: bool,
+  pub sort: SortMode,
+  pub reverse: bool,
+}
+
+impl Default for ReadOptions {
+  fn default() -> Self {
+    ReadOptions { show_hidden: true, sort: SortMode::default(), reverse: false }
+  }
+}
+
+/// Dot files
+pub fn is_hidden<P: AsRef<Path>>(path: P) -> bool {
+  path.as_ref().file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'))
+}
+
+pub fn read_items<P: AsRef<Path>>(path: P, options: ReadOptions) -> anyhow::Result<Vec<ItemInfo>> {
   let mut items = match fs::read_dir(&path) {
     Ok(read_dir) => read_dir
       .filter_map(|entry| {
         let entry = entry.ok()?;
         let filepath = entry.path();
-        let path = if filepath.is_file() && filepath.is_symlink() {
-          ItemPath::Symlink(ItemSymlink::File(filepath))
-        } else if filepath.is_dir() && filepath.is_symlink() {
-          ItemPath::Symlink(ItemSymlink::Dir(filepath))
-        } else if filepath.is_file() {
-          ItemPath::File(filepath)
-        } else if filepath.is_dir() {
-          ItemPath::Dir(filepath)
-        } else {
-          ItemPath::Unknown(filepath)
-        };
-        Some(ItemInfo { item: Item::Path(path), index: Some(0) })
+        if !options.show_hidden && is_hidden(&filepath) {
+          return None;
+        }
+        Some(ItemInfo { item: Item::Path(ItemPath::from_path(filepath)), index: Some(0) })
       })
       .collect::<Vec<_>>(),
     _ => {
@@ -30,7 +42,7 @@ pub fn read_items<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<ItemInfo>> {
     }
   };
 
-  items.sort_by_key(|item| item.get_path());
+  sort_items(&mut items, options.sort, options.reverse);
   Ok(
     items
       .iter_mut()
@@ -42,3 +54,30 @@ pub fn read_items<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<ItemInfo>> {
       .collect(),
   )
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::TempDir;
+
+  #[test]
+  fn test_is_hidden() {
+    assert!(is_hidden("/home/user/.config"));
+    assert!(!is_hidden("/home/user/config"));
+    assert!(!is_hidden("/"));
+  }
+
+  #[test]
+  fn test_read_items_hidden() {
+    let dir = TempDir::new("read_items_hidden");
+    fs::create_dir_all(dir.join(".hidden")).unwrap();
+    fs::create_dir_all(dir.join("visible")).unwrap();
+
+    let all = read_items(&dir, ReadOptions::default()).unwrap();
+    assert_eq!(all.len(), 2);
+    let visible = read_items(&dir, ReadOptions { show_hidden: false, ..Default::default() }).unwrap();
+    assert_eq!(visible.len(), 1);
+    assert_eq!(visible[0].get_path(), Some(dir.join("visible")));
+    assert_eq!(visible[0].index, Some(0));
+  }
+}
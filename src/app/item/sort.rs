@@ -0,0 +1,173 @@
+use std::{cmp::Ordering, fmt, fs, path::Path, time::SystemTime};
+
+use serde::Deserialize;
+
+use super::ItemInfo;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortMode {
+  /// Byte-wise path
+  #[default]
+  Name,
+  /// Numbers in names are compared by value, case-insensitive
+  Natural,
+  /// Newest first
+  Mtime,
+  /// Largest first
+  Size,
+  Extension,
+  DirsFirst,
+}
+
+impl SortMode {
+  pub fn next(&self) -> Self {
+    match self {
+      SortMode::Name => SortMode::Natural,
+      SortMode::Natural => SortMode::Mtime,
+      SortMode::Mtime => SortMode::Size,
+      SortMode::Size => SortMode::Extension,
+      SortMode::Extension => SortMode::DirsFirst,
+      SortMode::DirsFirst => SortMode::Name,
+    }
+  }
+}
+
+impl fmt::Display for SortMode {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      SortMode::Name => write!(f, "name"),
+      SortMode::Natural => write!(f, "natural"),
+      SortMode::Mtime => write!(f, "mtime"),
+      SortMode::Size => write!(f, "size"),
+      SortMode::Extension => write!(f, "extension"),
+      SortMode::DirsFirst => write!(f, "dirs_first"),
+    }
+  }
+}
+
+fn file_name(item: &ItemInfo) -> String {
+  item.generate_filename().unwrap_or_default()
+}
+
+fn extension(item: &ItemInfo) -> String {
+  item.get_path().and_then(|p| p.extension().map(|e| e.to_string_lossy().to_lowercase())).unwrap_or_default()
+}
+
+fn metadata<P: AsRef<Path>>(path: Option<P>) -> Option<fs::Metadata> {
+  fs::metadata(path?).ok()
+}
+
+/// Compare runs of digits by their value, and the rest case-insensitively
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+  let mut a = a.chars().peekable();
+  let mut b = b.chars().peekable();
+  loop {
+    match (a.peek().copied(), b.peek().copied()) {
+      (None, None) => return Ordering::Equal,
+      (None, Some(_)) => return Ordering::Less,
+      (Some(_), None) => return Ordering::Greater,
+      (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+        let take_number = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+          let mut digits = String::new();
+          while let Some(c) = chars.peek().filter(|c| c.is_ascii_digit()) {
+            digits.push(*c);
+            chars.next();
+          }
+          digits
+        };
+        let (x, y) = (take_number(&mut a), take_number(&mut b));
+        let (x_trimmed, y_trimmed) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+        let ordering = x_trimmed.len().cmp(&y_trimmed.len()).then_with(|| x_trimmed.cmp(y_trimmed));
+        if ordering != Ordering::Equal {
+          return ordering;
+        }
+      }
+      (Some(x), Some(y)) => {
+        let ordering = x.to_lowercase().cmp(y.to_lowercase());
+        if ordering != Ordering::Equal {
+          return ordering;
+        }
+        a.next();
+        b.next();
+      }
+    }
+  }
+}
+
+pub fn sort_items(items: &mut [ItemInfo], mode: SortMode, reverse: bool) {
+  match mode {
+    SortMode::Name => items.sort_by_key(|item| item.get_path()),
+    SortMode::Natural => items.sort_by(|a, b| natural_cmp(&file_name(a), &file_name(b))),
+    SortMode::Mtime => {
+      items.sort_by_cached_key(|item| {
+        std::cmp::Reverse(metadata(item.get_path()).and_then(|m| m.modified().ok()).unwrap_or(SystemTime::UNIX_EPOCH))
+      });
+    }
+    SortMode::Size => {
+      items.sort_by_cached_key(|item| std::cmp::Reverse(metadata(item.get_path()).map(|m| m.len()).unwrap_or(0)))
+    }
+    SortMode::Extension => {
+      items.sort_by(|a, b| extension(a).cmp(&extension(b)).then_with(|| natural_cmp(&file_name(a), &file_name(b))))
+    }
+    SortMode::DirsFirst => {
+      items.sort_by(|a, b| b.is_dir().cmp(&a.is_dir()).then_with(|| natural_cmp(&file_name(a), &file_name(b))))
+    }
+  }
+  if reverse {
+    items.reverse();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::app::{Item, ItemPath};
+
+  fn file(name: &str) -> ItemInfo {
+    ItemInfo { item: Item::Path(ItemPath::File(name.into())), index: None }
+  }
+
+  fn names(items: &[ItemInfo]) -> Vec<String> {
+    items.iter().map(file_name).collect()
+  }
+
+  #[test]
+  fn test_natural_cmp() {
+    assert_eq!(natural_cmp("file2", "file10"), Ordering::Less);
+    assert_eq!(natural_cmp("file10", "file2"), Ordering::Greater);
+    assert_eq!(natural_cmp("File1", "file1"), Ordering::Equal);
+    assert_eq!(natural_cmp("a", "ab"), Ordering::Less);
+    assert_eq!(natural_cmp("v007", "v7"), Ordering::Equal);
+  }
+
+  #[test]
+  fn test_sort_items() {
+    let mut items = vec![file("b10.rs"), file("b2.txt"), file("A.txt"), ItemInfo::create_dir_in_sort_tests("z")];
+    sort_items(&mut items, SortMode::Name, false);
+    assert_eq!(names(&items), ["A.txt", "b10.rs", "b2.txt", "z"]);
+    sort_items(&mut items, SortMode::Natural, false);
+    assert_eq!(names(&items), ["A.txt", "b2.txt", "b10.rs", "z"]);
+    sort_items(&mut items, SortMode::Extension, false);
+    assert_eq!(names(&items), ["z", "b10.rs", "A.txt", "b2.txt"]);
+    sort_items(&mut items, SortMode::DirsFirst, false);
+    assert_eq!(names(&items), ["z", "A.txt", "b2.txt", "b10.rs"]);
+    sort_items(&mut items, SortMode::DirsFirst, true);
+    assert_eq!(names(&items), ["b10.rs", "b2.txt", "A.txt", "z"]);
+  }
+
+  #[test]
+  fn test_next() {
+    let mut mode = SortMode::default();
+    for _ in 0..6 {
+      mode = mode.next();
+    }
+    assert_eq!(mode, SortMode::default());
+  }
+
+  impl ItemInfo {
+    fn create_dir_in_sort_tests(name: &str) -> Self {
+      Self { item: Item::create_dir(name), index: None }
+    }
+  }
+}
@@ -0,0 +1,457 @@
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+
+use super::AppMode;
+use crate::error::ConfigError;
+
+/// What a key does, independent of which key is bound to it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyAction {
+  MovePrevious,
+  MoveNext,
+  MoveParent,
+  MoveChild,
+  MoveHome,
+  MoveEnd,
+  MovePageUp,
+  MovePageDown,
+  ChangeDir,
+  Quit,
+  ToggleSearch,
+  DeleteChar,
+  ClearSearch,
+  ToggleRecursive,
+  ToggleGrep,
+  ToggleHidden,
+  CycleSort,
+  ToggleSortReverse,
+  OpenHistory,
+  AddBookmark,
+  RemoveBookmark,
+  OpenBookmarks,
+  OpenEditor,
+  Open,
+  Edit,
+  ToggleMark,
+  PrintMarked,
+  Yank,
+  Cut,
+  Paste,
+  Rename,
+  NewFile,
+  NewDir,
+  Delete,
+  Undo,
+  BulkRename,
+  Shell,
+  Print,
+  /// Unbind a default key
+  None,
+}
+
+impl KeyAction {
+  /// In the order of the usage table
+  const ALL: [KeyAction; 38] = [
+    KeyAction::MovePrevious,
+    KeyAction::MoveNext,
+    KeyAction::MoveParent,
+    KeyAction::MoveChild,
+    KeyAction::MoveHome,
+    KeyAction::MoveEnd,
+    KeyAction::MovePageUp,
+    KeyAction::MovePageDown,
+    KeyAction::ChangeDir,
+    KeyAction::Quit,
+    KeyAction::ToggleSearch,
+    KeyAction::DeleteChar,
+    KeyAction::ClearSearch,
+    KeyAction::ToggleRecursive,
+    KeyAction::ToggleGrep,
+    KeyAction::ToggleHidden,
+    KeyAction::CycleSort,
+    KeyAction::ToggleSortReverse,
+    KeyAction::OpenHistory,
+    KeyAction::AddBookmark,
+    KeyAction::RemoveBookmark,
+    KeyAction::OpenBookmarks,
+    KeyAction::OpenEditor,
+    KeyAction::Open,
+    KeyAction::Edit,
+    KeyAction::ToggleMark,
+    KeyAction::PrintMarked,
+    KeyAction::Yank,
+    KeyAction::Cut,
+    KeyAction::Paste,
+    KeyAction::Rename,
+    KeyAction::NewFile,
+    KeyAction::NewDir,
+    KeyAction::Delete,
+    KeyAction::Undo,
+    KeyAction::BulkRename,
+    KeyAction::Shell,
+    KeyAction::Print,
+  ];
+
+  pub fn description(&self) -> &str {
+    match self {
+      KeyAction::MovePrevious => "Move up",
+      KeyAction::MoveNext => "Move down",
+      KeyAction::MoveParent => "Move parent directory",
+      KeyAction::MoveChild => "Move Child directory",
+      KeyAction::MoveHome => "Move to top",
+      KeyAction::MoveEnd => "Move to bottom",
+      KeyAction::MovePageUp => "Skip a little and move up",
+      KeyAction::MovePageDown => "Skip a little and move down",
+      KeyAction::ChangeDir => "Change directory to current directory",
+      KeyAction::Quit => "Exit and return to original directory",
+      KeyAction::ToggleSearch => "Search mode switch (Char key will not work)",
+      KeyAction::DeleteChar => "Delete one character from the search string",
+      KeyAction::ClearSearch => "Delete all search strings",
+      KeyAction::ToggleRecursive => "Search all subdirectories recursively",
+      KeyAction::ToggleGrep => "Search inside the files of subdirectories",
+      KeyAction::ToggleHidden => "Show or hide hidden files",
+      KeyAction::CycleSort => "Change the sort order",
+      KeyAction::ToggleSortReverse => "Reverse the sort order",
+      KeyAction::OpenHistory => "Open the history of changed directories",
+      KeyAction::AddBookmark => "Bookmark the current directory",
+      KeyAction::RemoveBookmark => "Remove the current directory bookmark",
+      KeyAction::OpenBookmarks => "Open the bookmarks",
+      KeyAction::OpenEditor => "Open the current directory with its opener",
+      KeyAction::Open => "Open the selected item with its opener",
+      KeyAction::Edit => "Edit the file in $EDITOR at the line",
+      KeyAction::ToggleMark => "Mark or unmark the selected item",
+      KeyAction::PrintMarked => "Print the marked filepaths",
+      KeyAction::Yank => "Copy the marked or selected items",
+      KeyAction::Cut => "Cut the marked or selected items",
+      KeyAction::Paste => "Paste the copied or cut items here",
+      KeyAction::Rename => "Rename the selected item",
+      KeyAction::NewFile => "Create an empty file",
+      KeyAction::NewDir => "Create a directory",
+      KeyAction::Delete => "Delete the marked or selected items",
+      KeyAction::Undo => "Undo the last rename or move",
+      KeyAction::BulkRename => "Rename the marked or all items in $EDITOR",
+      KeyAction::Shell => "Run a shell command in current directory",
+      KeyAction::Print => "Print the selected filepath",
+      KeyAction::None => "Do nothing",
+    }
+  }
+}
+
+/// A key with modifiers, written like `ctrl+s`, `PageUp` or `H` in the config file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyChord {
+  pub code: KeyCode,
+  pub modifiers: KeyModifiers,
+}
+
+impl KeyChord {
+  /// `Shift` is already reflected in the character of `Char` keys
+  pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+    let modifiers = if let KeyCode::Char(_) = code { modifiers - KeyModifiers::SHIFT } else { modifiers };
+    KeyChord { code, modifiers }
+  }
+}
+
+impl From<&KeyEvent> for KeyChord {
+  fn from(key: &KeyEvent) -> Self {
+    KeyChord::new(key.code, key.modifiers)
+  }
+}
+
+impl FromStr for KeyChord {
+  type Err = ConfigError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let invalid = || ConfigError::InvalidValue(format!("unknown key '{s}'"));
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = s;
+    // `+` alone is a key, not a separator
+    while let Some((modifier, key)) = rest.split_once('+').filter(|(_, key)| !key.is_empty()) {
+      modifiers |= match modifier.to_lowercase().as_str() {
+        "ctrl" => KeyModifiers::CONTROL,
+        "alt" => KeyModifiers::ALT,
+        "shift" => KeyModifiers::SHIFT,
+        _ => return Err(invalid()),
+      };
+      rest = key;
+    }
+
+    let mut chars = rest.chars();
+    let code = match (chars.next(), chars.next()) {
+      (Some(c), None) => KeyCode::Char(c),
+      _ => match rest.to_lowercase().as_str() {
+        "enter" => KeyCode::Enter,
+        "esc" => KeyCode::Esc,
+        "tab" => KeyCode::Tab,
+        "backtab" => KeyCode::BackTab,
+        "backspace" => KeyCode::Backspace,
+        "delete" | "del" => KeyCode::Delete,
+        "insert" | "ins" => KeyCode::Insert,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "space" => KeyCode::Char(' '),
+        f => KeyCode::F(f.strip_prefix('f').and_then(|n| n.parse().ok()).ok_or_else(invalid)?),
+      },
+    };
+    // `Shift` is dropped from `Char` keys, so `shift+h` would be `h`
+    if let KeyCode::Char(c) = code
+      && modifiers.contains(KeyModifiers::SHIFT)
+    {
+      let hint = if c.is_ascii_alphabetic() {
+        format!("write '{}' instead", c.to_ascii_uppercase())
+      } else {
+        "write the shifted character instead".into()
+      };
+      return Err(ConfigError::InvalidValue(format!("key '{s}': {hint}")));
+    }
+    Ok(KeyChord::new(code, modifiers))
+  }
+}
+
+impl fmt::Display for KeyChord {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if self.modifiers.contains(KeyModifiers::CONTROL) {
+      write!(f, "Ctrl+")?;
+    }
+    if self.modifiers.contains(KeyModifiers::ALT) {
+      write!(f, "Alt+")?;
+    }
+    if self.modifiers.contains(KeyModifiers::SHIFT) {
+      write!(f, "Shift+")?;
+    }
+    match self.code {
+      KeyCode::Up => write!(f, "↑"),
+      KeyCode::Down => write!(f, "↓"),
+      KeyCode::Left => write!(f, "←"),
+      KeyCode::Right => write!(f, "→"),
+      KeyCode::Char(' ') => write!(f, "Space"),
+      KeyCode::Char(c) => write!(f, "{c}"),
+      KeyCode::Enter => write!(f, "Enter"),
+      KeyCode::Esc => write!(f, "Esc"),
+      KeyCode::Tab => write!(f, "Tab"),
+      KeyCode::BackTab => write!(f, "BackTab"),
+      KeyCode::Backspace => write!(f, "Backspace"),
+      KeyCode::Delete => write!(f, "Delete"),
+      KeyCode::Insert => write!(f, "Insert"),
+      KeyCode::Home => write!(f, "Home"),
+      KeyCode::End => write!(f, "End"),
+      KeyCode::PageUp => write!(f, "PageUp"),
+      KeyCode::PageDown => write!(f, "PageDown"),
+      code => write!(f, "{code}"),
+    }
+  }
+}
+
+/// `[keys.normal]` and `[keys.search]` tables of the config file
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct KeyConfig {
+  #[serde(default)]
+  pub normal: BTreeMap<String, KeyAction>,
+  #[serde(default)]
+  pub search: BTreeMap<String, KeyAction>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+  normal: Vec<(KeyChord, KeyAction)>,
+  search: Vec<(KeyChord, KeyAction)>,
+}
+
+impl Default for Keymap {
+  fn default() -> Self {
+    use KeyAction::*;
+    let normal = [
+      ("up", MovePrevious),
+      ("k", MovePrevious),
+      ("down", MoveNext),
+      ("j", MoveNext),
+      ("left", MoveParent),
+      ("h", MoveParent),
+      ("right", MoveChild),
+      ("l", MoveChild),
+      ("home", MoveHome),
+      ("end", MoveEnd),
+      ("pageup", MovePageUp),
+      ("pagedown", MovePageDown),
+      ("enter", ChangeDir),
+      ("c", ChangeDir),
+      (";", ChangeDir),
+      ("esc", Quit),
+      ("ctrl+c", Quit),
+      ("q", Quit),
+      ("insert", ToggleSearch),
+      ("ctrl+s", ToggleSearch),
+      ("backspace", DeleteChar),
+      ("delete", ClearSearch),
+      ("ctrl+f", ToggleRecursive),
+      ("ctrl+g", ToggleGrep),
+      (".", ToggleHidden),
+      ("s", CycleSort),
+      ("S", ToggleSortReverse),
+      ("H", OpenHistory),
+      ("m", AddBookmark),
+      ("M", RemoveBookmark),
+      ("B", OpenBookmarks),
+      ("V", OpenEditor),
+      ("o", Open),
+      ("e", Edit),
+      ("tab", ToggleMark),
+      ("P", PrintMarked),
+      ("y", Yank),
+      ("x", Cut),
+      ("v", Paste),
+      ("r", Rename),
+      ("n", NewFile),
+      ("N", NewDir),
+      ("d", Delete),
+      ("u", Undo),
+      ("R", BulkRename),
+      ("!", Shell),
+      ("p", Print),
+    ];
+    let search = [
+      ("up", MovePrevious),
+      ("down", MoveNext),
+      ("left", MoveParent),
+      ("right", MoveChild),
+      ("home", MoveHome),
+      ("end", MoveEnd),
+      ("pageup", MovePageUp),
+      ("pagedown", MovePageDown),
+      ("enter", ChangeDir),
+      ("esc", Quit),
+      ("ctrl+c", Quit),
+      ("insert", ToggleSearch),
+      ("ctrl+s", ToggleSearch),
+      ("backspace", DeleteChar),
+      ("delete", ClearSearch),
+      ("ctrl+f", ToggleRecursive),
+      ("ctrl+g", ToggleGrep),
+      ("tab", ToggleMark),
+    ];
+    let parse = |bindings: &[(&str, KeyAction)]| {
+      bindings.iter().map(|(key, action)| (key.parse().expect("default keys are valid"), *action)).collect()
+    };
+    Keymap { normal: parse(&normal), search: parse(&search) }
+  }
+}
+
+impl Keymap {
+  /// Default bindings overridden by the config file
+  pub fn with_config(config: &KeyConfig) -> Result<Self, ConfigError> {
+    let mut keymap = Self::default();
+    for (key, action) in &config.normal {
+      Self::bind(&mut keymap.normal, key.parse()?, *action);
+    }
+    for (key, action) in &config.search {
+      Self::bind(&mut keymap.search, key.parse()?, *action);
+    }
+    Ok(keymap)
+  }
+
+  fn bind(bindings: &mut Vec<(KeyChord, KeyAction)>, chord: KeyChord, action: KeyAction) {
+    match bindings.iter_mut().find(|(c, _)| *c == chord) {
+      Some(binding) => binding.1 = action,
+      None => bindings.push((chord, action)),
+    }
+  }
+
+  pub fn get(&self, mode: AppMode, key: &KeyEvent) -> Option<KeyAction> {
+    let bindings = match mode {
+      AppMode::Normal => &self.normal,
+      AppMode::Search => &self.search,
+    };
+    let chord = KeyChord::from(key);
+    bindings.iter().find(|(c, _)| *c == chord).map(|(_, action)| *action).filter(|action| *action != KeyAction::None)
+  }
+
+  fn keys(&self, action: KeyAction) -> Vec<KeyChord> {
+    self.normal.iter().filter(|(_, a)| *a == action).map(|(c, _)| *c).collect()
+  }
+
+  /// Key table of the normal mode shown by `--help`
+  pub fn usage(&self) -> String {
+    let rows = KeyAction::ALL
+      .iter()
+      .map(|action| {
+        let keys = self.keys(*action).iter().map(|c| c.to_string()).collect::<Vec<_>>().join(" ");
+        (keys, action.description())
+      })
+      .filter(|(keys, _)| !keys.is_empty())
+      .collect::<Vec<_>>();
+    let key_width = rows.iter().map(|(keys, _)| keys.chars().count()).max().unwrap_or(0).max(13);
+    let description_width = rows.iter().map(|(_, d)| d.chars().count()).max().unwrap_or(0).max(43);
+    let line = format!(" {}", "-".repeat(key_width + description_width + 5));
+
+    let mut usage = vec![
+      "ed\n".to_string(),
+      line.clone(),
+      format!("| {:<key_width$} | {:<description_width$} |", "  Key", "   Description"),
+      format!("|{}|{}|", "-".repeat(key_width + 2), "-".repeat(description_width + 2)),
+    ];
+    usage.extend(rows.iter().map(|(keys, d)| format!("| {keys:<key_width$} | {d:<description_width$} |")));
+    usage.push(line);
+    usage.join("\n")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_key() {
+    assert_eq!("j".parse::<KeyChord>().unwrap(), KeyChord::new(KeyCode::Char('j'), KeyModifiers::NONE));
+    assert_eq!("ctrl+s".parse::<KeyChord>().unwrap(), KeyChord::new(KeyCode::Char('s'), KeyModifiers::CONTROL));
+    assert_eq!("PageUp".parse::<KeyChord>().unwrap(), KeyChord::new(KeyCode::PageUp, KeyModifiers::NONE));
+    assert_eq!("+".parse::<KeyChord>().unwrap(), KeyChord::new(KeyCode::Char('+'), KeyModifiers::NONE));
+    assert_eq!("alt++".parse::<KeyChord>().unwrap(), KeyChord::new(KeyCode::Char('+'), KeyModifiers::ALT));
+    assert_eq!("f5".parse::<KeyChord>().unwrap(), KeyChord::new(KeyCode::F(5), KeyModifiers::NONE));
+    assert!("hyper+j".parse::<KeyChord>().is_err());
+    assert_eq!(
+      "shift+h".parse::<KeyChord>().unwrap_err().to_string(),
+      ConfigError::InvalidValue("key 'shift+h': write 'H' instead".into()).to_string()
+    );
+    assert!("ctrl+shift+1".parse::<KeyChord>().is_err());
+    assert_eq!("shift+tab".parse::<KeyChord>().unwrap(), KeyChord::new(KeyCode::Tab, KeyModifiers::SHIFT));
+    assert!("unknown".parse::<KeyChord>().is_err());
+  }
+
+  #[test]
+  fn test_shift_char() {
+    let key = KeyEvent::new(KeyCode::Char('H'), KeyModifiers::SHIFT);
+    assert_eq!(Keymap::default().get(AppMode::Normal, &key), Some(KeyAction::OpenHistory));
+  }
+
+  #[test]
+  fn test_with_config() {
+    let mut config = KeyConfig::default();
+    config.normal.insert("ctrl+n".into(), KeyAction::MoveNext);
+    config.normal.insert("j".into(), KeyAction::None);
+    config.search.insert("ctrl+n".into(), KeyAction::MoveNext);
+    let keymap = Keymap::with_config(&config).unwrap();
+    let ctrl_n = KeyEvent::new(KeyCode::Char('n'), KeyModifiers::CONTROL);
+    assert_eq!(keymap.get(AppMode::Normal, &ctrl_n), Some(KeyAction::MoveNext));
+    assert_eq!(keymap.get(AppMode::Search, &ctrl_n), Some(KeyAction::MoveNext));
+    assert_eq!(keymap.get(AppMode::Normal, &KeyEvent::new(KeyCode::Char('j'), KeyModifiers::NONE)), None);
+    assert_eq!(keymap.get(AppMode::Search, &KeyEvent::new(KeyCode::Char('q'), KeyModifiers::NONE)), None);
+  }
+
+  #[test]
+  fn test_usage() {
+    let usage = Keymap::default().usage();
+    assert!(usage.contains("| ↑ k           | Move up                                     |"));
+    assert!(usage.contains("| Enter c ;     | Change directory to current directory       |"));
+    assert!(usage.contains("| Esc Ctrl+c q  | Exit and return to original directory       |"));
+  }
+}
@@ -0,0 +1,248 @@
+use std::{
+  sync::{
+    Arc, Mutex, MutexGuard, PoisonError,
+    mpsc::{self, Receiver, Sender},
+  },
+  thread,
+  time::{Duration, Instant},
+};
+
+use super::{ItemInfo, ReadOptions};
+
+/// Threads reading at once, so that a read stuck on a slow directory does not hold up the others,
+/// while a hung mount cannot pile up threads with every move
+const WORKERS: usize = 4;
+
+/// The column a read is shown in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+  Grandparent,
+  Parent,
+  Items,
+  Child,
+}
+
+impl Column {
+  const ALL: [Column; 4] = [Column::Grandparent, Column::Parent, Column::Items, Column::Child];
+
+  /// The column it is shown in after moving into the selected item, `None` if it goes out of sight
+  pub fn after_move_child(self) -> Option<Column> {
+    match self {
+      Column::Grandparent => None,
+      Column::Parent => Some(Column::Grandparent),
+      Column::Items => Some(Column::Parent),
+      Column::Child => Some(Column::Items),
+    }
+  }
+
+  /// The column it is shown in after moving to the parent directory, `None` if it goes out of sight
+  pub fn after_move_parent(self) -> Option<Column> {
+    match self {
+      Column::Grandparent => Some(Column::Parent),
+      Column::Parent => Some(Column::Items),
+      Column::Items => Some(Column::Child),
+      Column::Child => None,
+    }
+  }
+}
+
+struct Request {
+  id: u64,
+  column: Column,
+  item: ItemInfo,
+  options: ReadOptions,
+}
+
+/// Children of `item` read by a background thread
+#[derive(Debug)]
+pub struct Loaded {
+  id: u64,
+  /// Where it is shown, which follows the moves made while it is read
+  pub column: Column,
+  pub item: ItemInfo,
+  /// The error is only shown, as the columns can be left empty
+  pub children: Result<Vec<ItemInfo>, String>,
+}
+
+/// The id of the latest request of each column not received yet, in the order of `Column::ALL`
+type Pending = Arc<Mutex<[Option<u64>; 4]>>;
+
+/// Reads the columns on `WORKERS` background threads, so that a slow directory does not block the keys.
+/// Only the latest request of each column is read and taken, the others are skipped or dropped as stale.
+#[derive(Debug)]
+pub struct Loader {
+  sender: Sender<Request>,
+  receiver: Receiver<Loaded>,
+  next_id: u64,
+  /// Shared with the workers, which skip the queued requests no longer in it
+  pending: Pending,
+}
+
+impl Loader {
+  pub fn start() -> Self {
+    let (sender, requests) = mpsc::channel::<Request>();
+    let (results, receiver) = mpsc::channel();
+    let requests = Arc::new(Mutex::new(requests));
+    let pending = Pending::default();
+    for _ in 0..WORKERS {
+      let (requests, results, pending) = (requests.clone(), results.clone(), pending.clone());
+      thread::spawn(move || {
+        loop {
+          // Only the waiting on the channel is locked, which is closed once the loader is dropped
+          let Ok(request) = lock(&requests).recv() else {
+            break;
+          };
+          if !lock(&pending).contains(&Some(request.id)) {
+            continue;
+          }
+          let children = request.item.generate_child_items(request.options).map_err(|e| e.to_string());
+          let loaded = Loaded { id: request.id, column: request.column, item: request.item, children };
+          if results.send(loaded).is_err() {
+            break;
+          }
+        }
+      });
+    }
+    Loader { sender, receiver, next_id: 0, pending }
+  }
+
+  pub fn request(&mut self, column: Column, item: ItemInfo, options: ReadOptions) {
+    self.next_id += 1;
+    let id = self.next_id;
+    lock(&self.pending)[column as usize] = Some(id);
+    let _ = self.sender.send(Request { id, column, item, options });
+  }
+
+  /// If any column is not received yet `true`
+  pub fn is_loading(&self) -> bool {
+    lock(&self.pending).iter().any(Option::is_some)
+  }
+
+  /// If the latest request of `column` is not received yet `true`
+  pub fn is_reading(&self, column: Column) -> bool {
+    lock(&self.pending)[column as usize].is_some()
+  }
+
+  /// Drop the result of the latest request of `column`
+  pub fn cancel(&mut self, column: Column) {
+    lock(&self.pending)[column as usize] = None;
+  }
+
+  /// Move the requests along with the columns, such as `Column::after_move_child`
+  pub fn shift(&mut self, shift: fn(Column) -> Option<Column>) {
+    let mut pending = lock(&self.pending);
+    let mut shifted = [None; 4];
+    for column in Column::ALL {
+      if let (Some(id), Some(to)) = (pending[column as usize], shift(column)) {
+        shifted[to as usize] = Some(id);
+      }
+    }
+    *pending = shifted;
+  }
+
+  /// The result of the latest request of any column, waiting for it up to `timeout`
+  pub fn receive(&mut self, timeout: Duration) -> Option<Loaded> {
+    // `None` waits until it is read
+    let deadline = Instant::now().checked_add(timeout);
+    while self.is_loading() {
+      let timeout = deadline.map_or(Duration::MAX, |deadline| deadline.saturating_duration_since(Instant::now()));
+      let mut loaded = self.receiver.recv_timeout(timeout).ok()?;
+      let mut pending = lock(&self.pending);
+      let Some(column) = Column::ALL.into_iter().find(|column| pending[*column as usize] == Some(loaded.id)) else {
+        continue;
+      };
+      pending[column as usize] = None;
+      loaded.column = column;
+      return Some(loaded);
+    }
+    None
+  }
+}
+
+/// Nothing panics while holding the locks, so a poisoned one is taken as it is
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+  mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs;
+
+  use super::*;
+  use crate::app::{Item, ItemPath};
+  use crate::testing::TempDir;
+
+  #[test]
+  fn test_loader() {
+    let root = TempDir::new("loader");
+    fs::create_dir_all(root.join("a")).unwrap();
+    fs::create_dir_all(root.join("b")).unwrap();
+    fs::write(root.join("b/file.txt"), "").unwrap();
+    let dir = |name: &str| ItemInfo { item: Item::Path(ItemPath::Dir(root.join(name))), index: None };
+    let wait = Duration::from_secs(10);
+
+    let mut loader = Loader::start();
+    assert!(!loader.is_loading());
+    loader.request(Column::Child, dir("a"), ReadOptions::default());
+    loader.request(Column::Child, dir("b"), ReadOptions::default());
+    assert!(loader.is_reading(Column::Child));
+    assert!(!loader.is_reading(Column::Items));
+
+    // Only the latest one is received
+    let loaded = loader.receive(wait).unwrap();
+    assert_eq!(loaded.column, Column::Child);
+    assert_eq!(loaded.item.get_path(), Some(root.join("b")));
+    let children = loaded.children.unwrap().iter().filter_map(|item| item.get_path()).collect::<Vec<_>>();
+    assert_eq!(children, [root.join("b/file.txt")]);
+    assert!(!loader.is_loading());
+    assert!(loader.receive(Duration::from_millis(10)).is_none());
+
+    // The columns are read side by side, and follow the moves
+    loader.request(Column::Items, dir("a"), ReadOptions::default());
+    loader.request(Column::Child, dir("b"), ReadOptions::default());
+    loader.shift(Column::after_move_child);
+    let mut columns = [loader.receive(wait).unwrap(), loader.receive(wait).unwrap()]
+      .map(|loaded| (loaded.column, loaded.item.get_path().unwrap()));
+    columns.sort_by_key(|(column, _)| *column as usize);
+    assert_eq!(columns, [(Column::Parent, root.join("a")), (Column::Items, root.join("b"))]);
+
+    loader.request(Column::Child, dir("a"), ReadOptions::default());
+    loader.shift(Column::after_move_parent);
+    assert!(!loader.is_loading());
+    loader.request(Column::Grandparent, dir("a"), ReadOptions::default());
+    loader.cancel(Column::Grandparent);
+    assert!(loader.receive(Duration::MAX).is_none());
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn test_loader_stuck() {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    use crate::app::GrepMatch;
+
+    let root = TempDir::new("loader_stuck");
+    fs::create_dir_all(root.join("a")).unwrap();
+    // Opening a FIFO to read it blocks until it is opened to write, as a hung mount would
+    let fifo = root.join("fifo");
+    let c_path = CString::new(fifo.as_os_str().as_bytes()).unwrap();
+    // SAFETY: `c_path` is a valid C string
+    assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+    let stuck = GrepMatch { path: fifo.clone(), line: 0, text: String::new() };
+
+    let mut loader = Loader::start();
+    loader.request(Column::Child, ItemInfo { item: Item::Grep(stuck), index: None }, ReadOptions::default());
+    loader.request(
+      Column::Items,
+      ItemInfo { item: Item::create_dir(root.join("a")), index: None },
+      ReadOptions::default(),
+    );
+    let loaded = loader.receive(Duration::from_secs(10)).unwrap();
+    assert_eq!(loaded.column, Column::Items);
+    assert!(loader.is_reading(Column::Child));
+
+    // The stuck read goes on once the FIFO is opened, and is received
+    drop(fs::OpenOptions::new().write(true).open(&fifo).unwrap());
+    assert_eq!(loader.receive(Duration::from_secs(10)).unwrap().column, Column::Child);
+  }
+}
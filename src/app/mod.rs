@@ -1,13 +1,37 @@
 mod _app;
+mod fileop;
+mod fuzzy;
+mod grep;
+mod highlight;
 mod item;
+mod keymap;
+mod loader;
+mod opener;
+mod picker;
+mod query;
+mod rename;
 mod run;
 mod search;
 mod state;
+mod status;
 mod ui;
+mod walk;
 
-pub use self::_app::{App, AppMode, app};
-pub use self::item::{Item, ItemInfo, ItemPath, read_items};
+pub use self::_app::{App, AppMode, app, open_tty, resume_terminal, suspend_terminal};
+pub use self::fileop::{Clipboard, move_to, utc_date_time};
+pub use self::grep::{Grep, GrepMatch};
+pub use self::highlight::Highlighter;
+pub use self::item::{Item, ItemInfo, ItemPath, ReadOptions, SortMode, read_items};
+pub use self::keymap::{KeyAction, KeyConfig, Keymap};
+pub use self::loader::{Column, Loader};
+pub use self::opener::{OpenMode, Opener, OpenerConfig, Openers, editor_command};
+pub use self::picker::{Picker, PickerEntry, PickerKind};
+pub use self::query::Query;
 pub use self::run::run;
 pub use self::search::Search;
 pub use self::state::{State, StatefulList};
+pub use self::status::{Prompt, PromptKind, Status};
 pub use self::ui::ui;
+pub use self::walk::{Walk, WalkOptions};
+
+pub(crate) use self::fuzzy::is_case_sensitive;
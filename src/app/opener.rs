@@ -0,0 +1,228 @@
+use std::{
+  path::Path,
+  process::{Command, Stdio},
+};
+
+use glob::{MatchOptions, Pattern};
+use serde::Deserialize;
+
+use crate::error::ConfigError;
+
+/// How the command of an opener is run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OpenMode {
+  /// In the background without the terminal, for GUI applications
+  #[default]
+  Detached,
+  /// On the terminal with the TUI suspended until it exits
+  Foreground,
+}
+
+/// `[[openers]]` tables of the config file
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct OpenerConfig {
+  pub pattern: String,
+  pub command: String,
+  #[serde(default)]
+  pub mode: OpenMode,
+}
+
+/// What the pattern of an opener is matched against
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Target {
+  /// `*.md`: the file name
+  Name,
+  /// `image/*`: the type guessed from the extension
+  Type,
+  /// `*/`: the directory name
+  Dir,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Opener {
+  pattern: Pattern,
+  target: Target,
+  /// Program and arguments, followed by the path
+  command: String,
+  pub mode: OpenMode,
+}
+
+impl Opener {
+  pub fn new(pattern: &str, command: &str, mode: OpenMode) -> Result<Self, ConfigError> {
+    if command.trim().is_empty() {
+      return Err(ConfigError::InvalidValue(format!("command of opener '{pattern}' must not be empty")));
+    }
+    let (pattern, target) = match pattern.strip_suffix('/') {
+      Some(dir) => (dir, Target::Dir),
+      None if pattern.contains('/') => (pattern, Target::Type),
+      None => (pattern, Target::Name),
+    };
+    let pattern =
+      Pattern::new(pattern).map_err(|e| ConfigError::InvalidValue(format!("opener pattern '{pattern}': {e}")))?;
+    Ok(Opener { pattern, target, command: command.into(), mode })
+  }
+
+  /// Opens any directory, for `editor`
+  pub fn dir(command: &str) -> Result<Self, ConfigError> {
+    Self::new("*/", command, OpenMode::Detached)
+  }
+
+  pub fn is_match(&self, path: &Path, is_dir: bool) -> bool {
+    // Extensions are often in uppercase, such as `IMG_0001.JPG`
+    let options = MatchOptions { case_sensitive: false, ..Default::default() };
+    let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    match self.target {
+      Target::Dir => is_dir && self.pattern.matches_with(&name, options),
+      Target::Name => !is_dir && self.pattern.matches_with(&name, options),
+      Target::Type => !is_dir && file_type(path).is_some_and(|t| self.pattern.matches_with(t, options)),
+    }
+  }
+
+  pub fn program(&self) -> &str {
+    self.command.split_whitespace().next().unwrap_or_default()
+  }
+
+  /// The command opening `path`, with no input or output when it is detached
+  pub fn command(&self, path: &Path) -> Command {
+    let mut args = self.command.split_whitespace();
+    let mut command = Command::new(args.next().unwrap_or_default());
+    command.args(args).arg(path);
+    if self.mode == OpenMode::Detached {
+      command.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null());
+      // In a process group of its own, out of reach of Ctrl+C and the hangup sent to the group of the TUI
+      #[cfg(unix)]
+      std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    }
+    command
+  }
+}
+
+/// Rules of the config file, the first matching one is used
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Openers {
+  rules: Vec<Opener>,
+}
+
+impl Openers {
+  pub fn with_config(config: &[OpenerConfig]) -> Result<Self, ConfigError> {
+    let rules =
+      config.iter().map(|rule| Opener::new(&rule.pattern, &rule.command, rule.mode)).collect::<Result<_, _>>()?;
+    Ok(Openers { rules })
+  }
+
+  pub fn find(&self, path: &Path, is_dir: bool) -> Option<&Opener> {
+    self.rules.iter().find(|rule| rule.is_match(path, is_dir))
+  }
+}
+
+/// `editor` opening `path` at `line` from `1`, in the way the editor takes it
+pub fn editor_command(editor: &str, path: &Path, line: Option<usize>) -> Command {
+  let mut args = editor.split_whitespace();
+  let program = args.next().unwrap_or("vi");
+  let mut command = Command::new(program);
+  command.args(args);
+  let Some(line) = line else {
+    command.arg(path);
+    return command;
+  };
+  let name = Path::new(program).file_stem().map(|name| name.to_string_lossy()).unwrap_or_default();
+  match name.as_ref() {
+    // `path:line`
+    "hx" | "helix" | "subl" => command.arg(format!("{}:{line}", path.display())),
+    "code" | "code-insiders" | "codium" => command.arg("-g").arg(format!("{}:{line}", path.display())),
+    // vi, vim, nvim, nano, emacs, micro, kak and most others take `+line`
+    _ => command.arg(format!("+{line}")).arg(path),
+  };
+  command
+}
+
+/// MIME type of well-known extensions
+fn file_type(path: &Path) -> Option<&'static str> {
+  let extension = path.extension()?.to_string_lossy().to_lowercase();
+  Some(match extension.as_str() {
+    "txt" | "log" => "text/plain",
+    "md" | "markdown" => "text/markdown",
+    "html" | "htm" => "text/html",
+    "css" => "text/css",
+    "csv" => "text/csv",
+    "json" => "application/json",
+    "toml" => "application/toml",
+    "yaml" | "yml" => "application/yaml",
+    "xml" => "application/xml",
+    "pdf" => "application/pdf",
+    "zip" => "application/zip",
+    "gz" | "tgz" => "application/gzip",
+    "tar" => "application/x-tar",
+    "png" => "image/png",
+    "jpg" | "jpeg" => "image/jpeg",
+    "gif" => "image/gif",
+    "webp" => "image/webp",
+    "svg" => "image/svg+xml",
+    "bmp" => "image/bmp",
+    "mp3" => "audio/mpeg",
+    "flac" => "audio/flac",
+    "wav" => "audio/wav",
+    "ogg" => "audio/ogg",
+    "mp4" => "video/mp4",
+    "mkv" => "video/x-matroska",
+    "webm" => "video/webm",
+    "mov" => "video/quicktime",
+    _ => return None,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn rule(pattern: &str, command: &str) -> OpenerConfig {
+    OpenerConfig { pattern: pattern.into(), command: command.into(), mode: OpenMode::Detached }
+  }
+
+  #[test]
+  fn test_find() {
+    let openers =
+      Openers::with_config(&[rule("*.md", "glow"), rule("image/*", "feh"), rule("*/", "code -n"), rule("*", "less")])
+        .unwrap();
+    let program = |path: &str, is_dir| openers.find(Path::new(path), is_dir).map(|opener| opener.program());
+    assert_eq!(program("/tmp/README.md", false), Some("glow"));
+    assert_eq!(program("/tmp/IMG_0001.JPG", false), Some("feh"));
+    assert_eq!(program("/tmp/a.md", true), Some("code"));
+    assert_eq!(program("/tmp/a.pdf", false), Some("less"));
+
+    let openers = Openers::with_config(&[rule("*.pdf", "zathura")]).unwrap();
+    assert!(openers.find(Path::new("/tmp/a.md"), false).is_none());
+    assert!(openers.find(Path::new("/tmp/pdf"), true).is_none());
+  }
+
+  #[test]
+  fn test_command() {
+    let opener = Opener::new("*.md", "glow -p", OpenMode::Foreground).unwrap();
+    let command = opener.command(Path::new("/tmp/a.md"));
+    assert_eq!(command.get_program(), "glow");
+    assert_eq!(command.get_args().collect::<Vec<_>>(), ["-p", "/tmp/a.md"]);
+  }
+
+  #[test]
+  fn test_editor_command() {
+    let args = |editor: &str, line| {
+      let command = editor_command(editor, Path::new("/tmp/a.rs"), line);
+      let mut args = vec![command.get_program().to_string_lossy().into_owned()];
+      args.extend(command.get_args().map(|arg| arg.to_string_lossy().into_owned()));
+      args
+    };
+    assert_eq!(args("nvim", Some(12)), ["nvim", "+12", "/tmp/a.rs"]);
+    assert_eq!(args("emacs -nw", Some(12)), ["emacs", "-nw", "+12", "/tmp/a.rs"]);
+    assert_eq!(args("/usr/bin/hx", Some(12)), ["/usr/bin/hx", "/tmp/a.rs:12"]);
+    assert_eq!(args("code --wait", Some(12)), ["code", "--wait", "-g", "/tmp/a.rs:12"]);
+    assert_eq!(args("nano", None), ["nano", "/tmp/a.rs"]);
+  }
+
+  #[test]
+  fn test_invalid() {
+    assert!(Openers::with_config(&[rule("*.md", " ")]).is_err());
+    assert!(Openers::with_config(&[rule("[md", "glow")]).is_err());
+  }
+}
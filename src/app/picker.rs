@@ -0,0 +1,145 @@
+use std::path::PathBuf;
+
+use ratatui::widgets::ListState;
+
+use super::State;
+use crate::history::is_match;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PickerEntry {
+  /// Bookmark name, empty for history
+  pub name: String,
+  pub label: String,
+  pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PickerKind {
+  History,
+  Bookmark,
+}
+
+impl PickerKind {
+  pub fn title(&self) -> &str {
+    match self {
+      PickerKind::History => "History",
+      PickerKind::Bookmark => "Bookmarks",
+    }
+  }
+}
+
+/// Overlay list of directories to jump to, filtered by the search text
+#[derive(Debug)]
+pub struct Picker {
+  pub kind: PickerKind,
+  pub entries: Vec<PickerEntry>,
+  pub list: Vec<PickerEntry>,
+  pub state: ListState,
+}
+
+impl Picker {
+  pub fn with_entries(kind: PickerKind, entries: Vec<PickerEntry>) -> Self {
+    let mut state = ListState::default();
+    state.select(Some(0));
+    Picker { kind, list: entries.clone(), entries, state }
+  }
+  /// Entries whose path or bookmark name contains `text`, in the same smart-case as `ed <query>`
+  pub fn filter(&mut self, text: &str) {
+    self.list = self
+      .entries
+      .iter()
+      .filter(|entry| is_match(&entry.path.to_string_lossy(), text) || is_match(&entry.name, text))
+      .cloned()
+      .collect();
+    self.state.select(Some(0));
+  }
+  pub fn selected_entry(&self) -> Option<&PickerEntry> {
+    self.list.get(self.state.selected()?)
+  }
+  pub fn selected_path(&self) -> Option<PathBuf> {
+    self.selected_entry().map(|entry| entry.path.clone())
+  }
+}
+
+impl State for Picker {
+  fn next(&mut self) -> usize {
+    let i = match self.state.selected() {
+      Some(i) => {
+        if i >= self.list.len().saturating_sub(1) {
+          0
+        } else {
+          i + 1
+        }
+      }
+      None => 0,
+    };
+    self.state.select(Some(i));
+    i
+  }
+  fn previous(&mut self) -> usize {
+    let i = match self.state.selected() {
+      Some(i) => {
+        if i == 0 {
+          self.list.len().saturating_sub(1)
+        } else {
+          i - 1
+        }
+      }
+      None => 0,
+    };
+    self.state.select(Some(i));
+    i
+  }
+  fn select(&mut self, index: usize) {
+    self.state.select(Some(index));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn entries() -> Vec<PickerEntry> {
+    ["/usr/src", "/home/user/project", "/home/user/project/api"]
+      .iter()
+      .map(|p| PickerEntry { name: String::new(), label: format!("{:>4}  {p}", 12), path: p.into() })
+      .collect()
+  }
+
+  #[test]
+  fn test_filter() {
+    let mut picker = Picker::with_entries(PickerKind::History, entries());
+    picker.filter("project");
+    assert_eq!(picker.list.len(), 2);
+    assert_eq!(picker.selected_path(), Some(PathBuf::from("/home/user/project")));
+    picker.filter("nothing");
+    assert_eq!(picker.selected_path(), None);
+
+    // Smart-case, and the visit count in the label is not matched
+    picker.filter("API");
+    assert!(picker.list.is_empty());
+    picker.filter("Project");
+    assert!(picker.list.is_empty());
+    picker.filter("api");
+    assert_eq!(picker.selected_path(), Some(PathBuf::from("/home/user/project/api")));
+    picker.filter("12");
+    assert!(picker.list.is_empty());
+  }
+
+  #[test]
+  fn test_next_previous() {
+    let mut picker = Picker::with_entries(PickerKind::History, entries());
+    assert_eq!(picker.next(), 1);
+    assert_eq!(picker.next(), 2);
+    assert_eq!(picker.next(), 0);
+    assert_eq!(picker.previous(), 2);
+  }
+
+  #[test]
+  fn test_empty() {
+    let mut picker = Picker::with_entries(PickerKind::History, vec![]);
+    assert_eq!(picker.next(), 0);
+    assert_eq!(picker.previous(), 0);
+    assert_eq!(picker.selected_path(), None);
+  }
+}
@@ -0,0 +1,148 @@
+use std::path::Path;
+
+use glob::{MatchOptions, Pattern};
+use regex::{Regex, RegexBuilder};
+
+use super::fuzzy::{FuzzyMatch, fuzzy_match, is_case_sensitive};
+use super::grep::find;
+
+const REGEX_PREFIX: &str = "re:";
+const GLOB_PREFIX: &str = "glob:";
+
+/// How the search text is matched, chosen by its prefix
+#[derive(Debug, Clone)]
+pub enum Query {
+  Fuzzy(String),
+  /// `re:` followed by a regular expression
+  Regex(Regex),
+  /// `glob:` followed by a pattern, or a search text containing `*` or `?`
+  Glob(Pattern),
+}
+
+impl Default for Query {
+  fn default() -> Self {
+    Query::Fuzzy(String::new())
+  }
+}
+
+impl Query {
+  /// `Err` has a one-line message to show in the search row
+  pub fn parse(text: &str) -> Result<Self, String> {
+    if let Some(re) = text.strip_prefix(REGEX_PREFIX) {
+      return RegexBuilder::new(re)
+        .case_insensitive(!is_case_sensitive(re))
+        .build()
+        .map(Query::Regex)
+        // The last line of a syntax error is the reason, the rest points at the position
+        .map_err(|e| e.to_string().lines().last().unwrap_or_default().to_string());
+    }
+    let glob = text.strip_prefix(GLOB_PREFIX).or(Some(text).filter(|text| text.contains(['*', '?'])));
+    if let Some(glob) = glob {
+      return Pattern::new(glob).map(Query::Glob).map_err(|e| format!("error: {e}"));
+    }
+    Ok(Query::Fuzzy(text.into()))
+  }
+
+  /// Match a file name, or a path in the recursive search.
+  /// Only the fuzzy query ranks the results, the others keep them in order.
+  pub fn find(&self, text: &str) -> Option<FuzzyMatch> {
+    match self {
+      Query::Fuzzy(pattern) => fuzzy_match(text, pattern),
+      Query::Regex(re) => {
+        let m = re.find(text)?;
+        let start = text[..m.start()].chars().count();
+        Some(FuzzyMatch { score: 0, positions: (start..start + m.as_str().chars().count()).collect() })
+      }
+      // The whole file name is matched as the shell would
+      Query::Glob(pattern) => {
+        let name = Path::new(text).file_name()?.to_string_lossy();
+        let offset = text.chars().count() - name.chars().count();
+        pattern
+          .matches_with(&name, glob_options(pattern))
+          .then(|| FuzzyMatch { score: 0, positions: (offset..text.chars().count()).collect() })
+      }
+    }
+  }
+
+  /// Match a line of file contents
+  pub fn is_match_line(&self, line: &str) -> bool {
+    self.find_line(line).is_some()
+  }
+
+  /// Char indices of `line` matched by `is_match_line`: the first occurrence, or the whole line for a glob.
+  /// The fuzzy query is found as it is, in smart-case as grep does.
+  pub fn find_line(&self, line: &str) -> Option<Vec<usize>> {
+    let range = match self {
+      Query::Fuzzy(pattern) if pattern.is_empty() => return Some(vec![]),
+      Query::Fuzzy(pattern) => return find(line, pattern).map(|range| range.collect()),
+      Query::Regex(re) => re.find(line).map(|m| m.range()),
+      Query::Glob(pattern) => pattern.matches_with(line, glob_options(pattern)).then_some(0..line.len()),
+    }?;
+    let start = line[..range.start].chars().count();
+    Some((start..start + line[range].chars().count()).collect())
+  }
+}
+
+fn glob_options(pattern: &Pattern) -> MatchOptions {
+  MatchOptions {
+    case_sensitive: is_case_sensitive(pattern.as_str()),
+    require_literal_separator: true,
+    require_literal_leading_dot: true,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn positions(query: &str, text: &str) -> Option<Vec<usize>> {
+    Query::parse(query).unwrap().find(text).map(|m| m.positions)
+  }
+
+  #[test]
+  fn test_parse() {
+    assert!(matches!(Query::parse("src"), Ok(Query::Fuzzy(_))));
+    assert!(matches!(Query::parse("re:^src$"), Ok(Query::Regex(_))));
+    assert!(matches!(Query::parse("*.rs"), Ok(Query::Glob(_))));
+    assert!(matches!(Query::parse("glob:src"), Ok(Query::Glob(_))));
+    assert_eq!(Query::parse("re:(src").unwrap_err(), "error: unclosed group");
+    assert!(Query::parse("glob:[src").is_err());
+  }
+
+  #[test]
+  fn test_regex() {
+    assert_eq!(positions("re:\\d+", "libfoo-12.so"), Some(vec![7, 8]));
+    assert_eq!(positions("re:^foo", "libfoo.so"), None);
+    assert_eq!(positions("re:FOO", "libfoo.so"), None);
+    assert_eq!(positions("re:foo", "libFOO.so"), Some(vec![3, 4, 5]));
+  }
+
+  #[test]
+  fn test_glob() {
+    assert_eq!(positions("*.rs", "main.rs"), Some(vec![0, 1, 2, 3, 4, 5, 6]));
+    assert_eq!(positions("*.rs", "main.rs.bak"), None);
+    assert_eq!(positions("*.rs", ".hidden.rs"), None);
+    assert_eq!(positions("*.rs", "src/main.rs"), Some(vec![4, 5, 6, 7, 8, 9, 10]));
+    assert_eq!(positions("lib?.so", "liba.so"), Some(vec![0, 1, 2, 3, 4, 5, 6]));
+    assert_eq!(positions("*.RS", "main.rs"), None);
+  }
+
+  #[test]
+  fn test_is_match_line() {
+    assert!(Query::parse("fn main").unwrap().is_match_line("pub fn main() {"));
+    assert!(!Query::parse("fn Main").unwrap().is_match_line("pub fn main() {"));
+    assert!(Query::parse("re:fn \\w+\\(").unwrap().is_match_line("pub fn main() {"));
+    assert!(Query::parse("glob:pub *").unwrap().is_match_line("pub fn main() {"));
+  }
+
+  #[test]
+  fn test_find_line() {
+    let find_line = |query: &str, line: &str| Query::parse(query).unwrap().find_line(line);
+    assert_eq!(find_line("main", "pub fn main() {"), Some(vec![7, 8, 9, 10]));
+    assert_eq!(find_line("re:m\\w+", "ことば main"), Some(vec![4, 5, 6, 7]));
+    assert_eq!(find_line("glob:pub *", "pub fn"), Some(vec![0, 1, 2, 3, 4, 5]));
+    assert_eq!(find_line("Main", "pub fn main() {"), None);
+    assert_eq!(find_line("foo", "ことば Foo"), Some(vec![4, 5, 6]));
+    assert_eq!(find_line("", "pub fn"), Some(vec![]));
+  }
+}
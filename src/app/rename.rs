@@ -0,0 +1,176 @@
+use std::{
+  collections::HashSet,
+  path::{Component, Path, PathBuf},
+};
+
+use super::fileop::{exists, move_to};
+use crate::error::{AppError, FileSystemError};
+
+/// A line of the edited names which differs from the original
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rename {
+  pub from: PathBuf,
+  pub to: PathBuf,
+  /// Part of renames leading back to itself, such as a swap
+  pub cycle: bool,
+}
+
+/// One line per path, relative to `base` if it is below it
+pub fn to_lines(paths: &[PathBuf], base: &Path) -> Result<String, FileSystemError> {
+  let mut text = String::new();
+  for path in paths {
+    let line = path.strip_prefix(base).unwrap_or(path).to_string_lossy();
+    if line.contains('\n') {
+      return Err(FileSystemError::InvalidPath(path.to_string_lossy().into()));
+    }
+    text.push_str(&line);
+    text.push('\n');
+  }
+  Ok(text)
+}
+
+/// Compare the edited lines with `paths` line by line.
+/// Two items renamed to the same path, or a rename to an existing path which is not renamed itself, is an error,
+/// and so is a changed line leaving `base`, such as an absolute path or one with `..`.
+pub fn plan(paths: &[PathBuf], edited: &str, base: &Path) -> Result<Vec<Rename>, AppError> {
+  let lines = edited.lines().collect::<Vec<_>>();
+  if lines.len() != paths.len() {
+    return Err(AppError::Other(format!("{} lines are expected, but {} are given", paths.len(), lines.len())));
+  }
+  if let Some(i) = lines.iter().position(|line| line.is_empty()) {
+    return Err(AppError::Other(format!("Line {} is empty", i + 1)));
+  }
+
+  let mut renames = vec![];
+  for (from, line) in paths.iter().zip(lines) {
+    let to = base.join(line);
+    if *from == to {
+      continue;
+    }
+    if !Path::new(line).components().all(|c| matches!(c, Component::CurDir | Component::Normal(_))) {
+      return Err(FileSystemError::InvalidPath(line.into()).into());
+    }
+    renames.push(Rename { from: from.clone(), to, cycle: false });
+  }
+
+  let sources = renames.iter().map(|r| r.from.as_path()).collect::<HashSet<_>>();
+  let mut targets = HashSet::new();
+  for rename in &renames {
+    if !targets.insert(rename.to.as_path()) {
+      return Err(FileSystemError::DuplicateTarget(rename.to.to_string_lossy().into()).into());
+    }
+    if exists(&rename.to) && !sources.contains(rename.to.as_path()) {
+      return Err(FileSystemError::AlreadyExists(rename.to.to_string_lossy().into()).into());
+    }
+  }
+
+  let cycles = renames.iter().map(|rename| is_cycle(&renames, rename)).collect::<Vec<_>>();
+  for (rename, cycle) in renames.iter_mut().zip(cycles) {
+    rename.cycle = cycle;
+  }
+  Ok(renames)
+}
+
+fn is_cycle(renames: &[Rename], start: &Rename) -> bool {
+  let mut current = &start.to;
+  for _ in 0..renames.len() {
+    match renames.iter().find(|r| r.from == *current) {
+      Some(next) if next.to == start.from => return true,
+      Some(next) => current = &next.to,
+      None => return false,
+    }
+  }
+  false
+}
+
+/// Rename all, moving the items renamed to the path of another item to a temporary name first.
+/// Returns the moves done in order, all of which are moved back on failure.
+pub fn apply(renames: &[Rename]) -> Result<Vec<(PathBuf, PathBuf)>, FileSystemError> {
+  let sources = renames.iter().map(|r| r.from.as_path()).collect::<HashSet<_>>();
+  let (chained, direct): (Vec<_>, Vec<_>) = renames.iter().partition(|r| sources.contains(r.to.as_path()));
+  let temps = chained.iter().enumerate().map(|(i, r)| temp_path(&r.from, i)).collect::<Vec<_>>();
+
+  let moves = chained
+    .iter()
+    .zip(&temps)
+    .map(|(r, temp)| (r.from.clone(), temp.clone()))
+    .chain(direct.iter().map(|r| (r.from.clone(), r.to.clone())))
+    .chain(chained.iter().zip(&temps).map(|(r, temp)| (temp.clone(), r.to.clone())))
+    .collect::<Vec<_>>();
+  for (i, (from, to)) in moves.iter().enumerate() {
+    if let Err(e) = move_to(from, to) {
+      for (from, to) in moves[..i].iter().rev() {
+        let _ = move_to(to, from);
+      }
+      return Err(e);
+    }
+  }
+  Ok(moves)
+}
+
+fn temp_path(path: &Path, i: usize) -> PathBuf {
+  let name = path.file_name().unwrap_or_default().to_string_lossy();
+  path.with_file_name(format!(".{name}.ed-rename-{}-{i}", std::process::id()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::TempDir;
+  use std::fs;
+
+  #[test]
+  fn test_plan() {
+    let dir = TempDir::new("rename_plan");
+    for name in ["a", "b", "c"] {
+      fs::write(dir.join(name), name).unwrap();
+    }
+    let paths = ["a", "b", "c"].map(|name| dir.join(name));
+    assert_eq!(to_lines(&paths, &dir).unwrap(), "a\nb\nc\n");
+
+    let renames = plan(&paths, "b\na\nd\n", &dir).unwrap();
+    let cycles = renames.iter().map(|r| (r.to.strip_prefix(&dir).unwrap().to_path_buf(), r.cycle)).collect::<Vec<_>>();
+    assert_eq!(cycles, [(PathBuf::from("b"), true), (PathBuf::from("a"), true), (PathBuf::from("d"), false)]);
+    assert!(plan(&paths, "a\nb\nc", &dir).unwrap().is_empty());
+
+    assert!(matches!(plan(&paths, "a\nb\n", &dir), Err(AppError::Other(_))));
+    assert!(matches!(plan(&paths, "d\nd\nc\n", &dir), Err(AppError::FileSystem(FileSystemError::DuplicateTarget(_)))));
+    // `b` is not renamed
+    assert!(matches!(plan(&paths, "b\nb\nc\n", &dir), Err(AppError::FileSystem(FileSystemError::AlreadyExists(_)))));
+    assert_eq!(plan(&paths, "b\nb2\nc\n", &dir).unwrap().len(), 2);
+    // Only paths below `dir`
+    assert_eq!(plan(&paths, "a\nsub/b\nc\n", &dir).unwrap()[0].to, dir.join("sub/b"));
+    for outside in ["/tmp/b", "../b", "sub/../../b"] {
+      let edited = format!("a\n{outside}\nc\n");
+      assert!(matches!(plan(&paths, &edited, &dir), Err(AppError::FileSystem(FileSystemError::InvalidPath(_)))));
+    }
+    // Unchanged, though it is not below `dir`
+    let outside = [PathBuf::from("/tmp/ed_test_rename_outside")];
+    assert!(plan(&outside, "/tmp/ed_test_rename_outside\n", &dir).unwrap().is_empty());
+  }
+
+  #[test]
+  fn test_apply() {
+    let dir = TempDir::new("rename_apply");
+    for name in ["a", "b", "c"] {
+      fs::write(dir.join(name), name).unwrap();
+    }
+    let paths = ["a", "b", "c"].map(|name| dir.join(name));
+    // A swap
+    let renames = plan(&paths, "b\na\nd\n", &dir).unwrap();
+    let moves = apply(&renames).unwrap();
+    assert_eq!(moves.len(), 5);
+    assert_eq!(fs::read_to_string(dir.join("a")).unwrap(), "b");
+    assert_eq!(fs::read_to_string(dir.join("b")).unwrap(), "a");
+    assert_eq!(fs::read_to_string(dir.join("d")).unwrap(), "c");
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 3);
+
+    // Moved back when the second one fails
+    let renames = [
+      Rename { from: dir.join("a"), to: dir.join("e"), cycle: false },
+      Rename { from: dir.join("missing"), to: dir.join("f"), cycle: false },
+    ];
+    assert!(apply(&renames).is_err());
+    assert!(dir.join("a").exists() && !dir.join("e").exists());
+  }
+}
@@ -1,18 +1,39 @@
-use std::process::Command;
+use std::{
+  env, fs,
+  io::{self, Write},
+  mem,
+  path::PathBuf,
+  process::{Command, ExitStatus, Stdio},
+  thread,
+  time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::crossterm::terminal::enable_raw_mode;
 use ratatui::{Terminal, backend::Backend};
 
+use crate::error::{AppError, FileSystemError};
 use crate::{Log, action::Action};
 
-use super::{App, AppMode};
+use super::status::describe;
+use super::{
+  App, AppMode, KeyAction, OpenMode, PromptKind, State, Status, editor_command, open_tty, resume_terminal,
+  suspend_terminal,
+};
 
-pub fn run<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> anyhow::Result<Action> {
+pub fn run<B: Backend + Write>(terminal: &mut Terminal<B>, mut app: App) -> anyhow::Result<Action> {
   if app.config.is_log() {
     Log::init()?;
   }
   loop {
     terminal.draw(|f| super::ui(f, &mut app))?;
+    // Redraw while the recursive search or grep is finding results, or the columns are read
+    let interval = Duration::from_millis(if app.is_loading() || app.grep_restart.is_some() { 20 } else { 100 });
+    if (app.is_subtree_searching() || app.is_loading()) && !event::poll(interval)? {
+      app.poll_walk()?;
+      app.finish_loading(Some(Duration::ZERO))?;
+      continue;
+    }
     if let Ok(Event::Key(key)) = event::read() {
       if key.kind == KeyEventKind::Release {
         continue;
@@ -20,100 +41,375 @@ pub fn run<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> anyhow::Resu
       if app.config.is_log() {
         Log::write(&app, &key);
       }
-      match app.mode {
-        AppMode::Normal => {
-          match key.code {
-            // finish
-            KeyCode::Char('c') if key.modifiers == KeyModifiers::CONTROL => return Ok(Action::Keep),
-            KeyCode::Char('q') => return Ok(Action::Keep),
-            KeyCode::Esc => return Ok(Action::Keep),
-
-            // change directory
-            KeyCode::Char('c') => return Ok(Action::Change(app.wd)),
-            KeyCode::Char(';') => return Ok(Action::Change(app.wd)),
-            KeyCode::Enter => return Ok(Action::Change(app.wd)),
-
-            // move
-            KeyCode::Home => app.move_home()?,
-            KeyCode::End => app.move_end()?,
-            KeyCode::PageUp => app.move_page_up()?,
-            KeyCode::PageDown => app.move_page_down()?,
-            KeyCode::Char('j') => app.move_next()?,
-            KeyCode::Down => app.move_next()?,
-            KeyCode::Char('k') => app.move_previous()?,
-            KeyCode::Up => app.move_previous()?,
-            KeyCode::Char('h') => app.move_parent()?,
-            KeyCode::Left => app.move_parent()?,
-            KeyCode::Char('l') => app.move_child()?,
-            KeyCode::Right => app.move_child()?,
-
-            // search
-            KeyCode::Char('s') if key.modifiers == KeyModifiers::CONTROL => app.mode = AppMode::Search,
-            KeyCode::Insert => app.mode = AppMode::Search,
-            KeyCode::Backspace => {
-              app.search.text.pop();
-              app.update_search_effect()?;
-            }
-            KeyCode::Delete => {
-              app.search.text.clear();
-              app.update_search_effect()?;
-            }
-
-            // Execute command
-            KeyCode::Char('V') => {
-              Command::new("code").arg(&app.wd).output()?;
-            }
-            // KeyCode::Char('L') => {
-            //   Command::new("lapce").arg(&app.wd).output()?;
-            // }
-
-            // print selected filepath
-            KeyCode::Char('p') => return Ok(Action::Print(app.get_selected_filepath()?)),
-
-            _ => {}
+      // The status of the last operation is shown until the next key
+      app.status = None;
+      if app.prompt.is_some() {
+        run_prompt(terminal, &mut app, key)?;
+        continue;
+      }
+      if app.picker.is_some() {
+        run_picker(&mut app, key)?;
+        continue;
+      }
+      match app.config.keymap().get(app.mode, &key) {
+        Some(key_action) => {
+          if let Some(action) = execute(terminal, &mut app, key_action)? {
+            return Ok(action);
           }
         }
-        AppMode::Search => {
-          match key.code {
-            // finish
-            KeyCode::Char('c') if key.modifiers == KeyModifiers::CONTROL => return Ok(Action::Keep),
-            KeyCode::Esc => return Ok(Action::Keep),
-
-            // change directory
-            KeyCode::Enter => return Ok(Action::Change(app.wd)),
-
-            // search
-            KeyCode::Char('s') if key.modifiers == KeyModifiers::CONTROL => app.mode = AppMode::Normal,
-            KeyCode::Insert => app.mode = AppMode::Normal,
-
-            // input
-            KeyCode::Char(c) => {
-              app.search.text.push(c);
-              app.update_search_effect()?;
-            }
-            KeyCode::Backspace => {
-              app.search.text.pop();
-              app.update_search_effect()?;
-            }
-            KeyCode::Delete => {
-              app.search.text.clear();
-              app.update_search_effect()?;
-            }
-
-            // move
-            KeyCode::Home => app.move_home()?,
-            KeyCode::End => app.move_end()?,
-            KeyCode::PageUp => app.move_page_up()?,
-            KeyCode::PageDown => app.move_page_down()?,
-            KeyCode::Down => app.move_next()?,
-            KeyCode::Up => app.move_previous()?,
-            KeyCode::Left => app.move_parent()?,
-            KeyCode::Right => app.move_child()?,
-
-            _ => {}
+        // input
+        None => {
+          if let (AppMode::Search, KeyCode::Char(c)) = (app.mode, key.code)
+            && (key.modifiers - KeyModifiers::SHIFT).is_empty()
+          {
+            app.search.text.push(c);
+            app.update_search_effect()?;
           }
         }
       }
     }
   }
 }
+
+/// Returns `Some` when the TUI should finish with the action
+fn execute<B: Backend + Write>(
+  terminal: &mut Terminal<B>,
+  app: &mut App,
+  key_action: KeyAction,
+) -> anyhow::Result<Option<Action>> {
+  match key_action {
+    // finish
+    KeyAction::Quit => return Ok(Some(Action::Keep)),
+
+    // change directory
+    KeyAction::ChangeDir if app.pick.is_some() => return Ok(app.print_marked()),
+    KeyAction::ChangeDir if app.is_subtree_search() => app.jump_to_result()?,
+    KeyAction::ChangeDir => return Ok(Some(Action::Change(app.wd.clone()))),
+
+    // move
+    KeyAction::MoveHome => app.move_home()?,
+    KeyAction::MoveEnd => app.move_end()?,
+    KeyAction::MovePageUp => app.move_page_up()?,
+    KeyAction::MovePageDown => app.move_page_down()?,
+    KeyAction::MoveNext => app.move_next()?,
+    KeyAction::MovePrevious => app.move_previous()?,
+    KeyAction::MoveParent => app.move_parent()?,
+    KeyAction::MoveChild => app.move_child()?,
+
+    // search
+    KeyAction::ToggleSearch => {
+      app.mode = match app.mode {
+        AppMode::Normal => AppMode::Search,
+        AppMode::Search => AppMode::Normal,
+      }
+    }
+    KeyAction::DeleteChar => {
+      app.search.text.pop();
+      app.update_search_effect()?;
+    }
+    KeyAction::ClearSearch => {
+      app.search.text.clear();
+      app.update_search_effect()?;
+    }
+    KeyAction::ToggleRecursive => app.toggle_recursive()?,
+    KeyAction::ToggleGrep => app.toggle_grep()?,
+
+    // filter
+    KeyAction::ToggleHidden => app.toggle_hidden()?,
+    KeyAction::CycleSort => app.cycle_sort()?,
+    KeyAction::ToggleSortReverse => app.toggle_sort_reverse()?,
+
+    // history
+    KeyAction::OpenHistory => app.open_history()?,
+
+    // bookmark
+    KeyAction::AddBookmark => app.add_bookmark(),
+    KeyAction::RemoveBookmark => app.remove_bookmark(),
+    KeyAction::OpenBookmarks => app.open_bookmarks()?,
+
+    // Execute command
+    KeyAction::OpenEditor => open(terminal, app, app.wd.clone())?,
+    // Nothing is opened for an empty directory or a line of file contents
+    KeyAction::Open => {
+      if let Ok(path) = app.get_selected_filepath() {
+        open(terminal, app, path)?;
+      }
+    }
+    KeyAction::Edit => edit(terminal, app)?,
+
+    // mark
+    KeyAction::ToggleMark => app.toggle_mark()?,
+    KeyAction::PrintMarked => return Ok(app.print_marked()),
+
+    // file operation
+    KeyAction::Yank => app.yank(false)?,
+    KeyAction::Cut => app.yank(true)?,
+    KeyAction::Paste => app.paste()?,
+    KeyAction::Rename => app.open_rename(),
+    KeyAction::NewFile => app.open_new_file(),
+    KeyAction::NewDir => app.open_new_dir(),
+    KeyAction::Delete => app.open_delete(),
+    KeyAction::Undo => app.undo()?,
+    KeyAction::Shell => app.open_shell(),
+    KeyAction::BulkRename => {
+      let piped = app.piped;
+      app.open_bulk_rename(|text| edit_text(terminal, text, piped))?;
+    }
+
+    // print selected filepath
+    KeyAction::Print => return Ok(Some(Action::Print(app.get_selected_filepath()?))),
+
+    KeyAction::None => {}
+  }
+  Ok(None)
+}
+
+/// Edit `text` in the editor of `editor()` with the TUI suspended.
+/// `piped`: stdout is a pipeline, so the editor is shown on the terminal instead
+fn edit_text<B: Backend + Write>(terminal: &mut Terminal<B>, text: &str, piped: bool) -> Result<String, AppError> {
+  let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+  let path = env::temp_dir().join(format!("ed-rename-{}-{nanos}.txt", std::process::id()));
+  fs::OpenOptions::new()
+    .write(true)
+    .create_new(true)
+    .open(&path)
+    .and_then(|mut file| file.write_all(text.as_bytes()))
+    .map_err(FileSystemError::FileWriteFailed)?;
+
+  let editor = editor();
+  let mut command = editor_command(&editor, &path, None);
+  let status = run_suspended(terminal, &mut command, piped, false);
+
+  let edited = fs::read_to_string(&path).map_err(FileSystemError::FileReadFailed);
+  let _ = fs::remove_file(&path);
+  let status = status?;
+  if !status.success() {
+    return Err(AppError::Other(format!("{editor} exited with {status}")));
+  }
+  Ok(edited?)
+}
+
+/// Run `command` through `$SHELL -c` in the working directory, and re-read the directories after it
+fn run_shell<B: Backend + Write>(terminal: &mut Terminal<B>, app: &mut App, command: &str) -> anyhow::Result<()> {
+  if command.trim().is_empty() {
+    return Ok(());
+  }
+  let shell = env::var("SHELL").ok().filter(|shell| !shell.is_empty()).unwrap_or_else(|| "sh".into());
+  let mut shell_command = Command::new(&shell);
+  shell_command.arg("-c").arg(app.expand_command(command)).current_dir(app.command_dir());
+  match run_suspended(terminal, &mut shell_command, app.piped, true) {
+    Ok(status) if status.success() => {}
+    Ok(status) => app.status = Some(Status::Error(format!("{command}: exited with {status}"))),
+    Err(e) => app.status = Some(Status::Error(format!("{command}: {e}"))),
+  }
+  app.refresh()
+}
+
+/// Open `path` with the first opener matching it
+fn open<B: Backend + Write>(terminal: &mut Terminal<B>, app: &mut App, path: PathBuf) -> anyhow::Result<()> {
+  let Some(opener) = app.config.opener(&path) else {
+    app.status = Some(Status::Error(format!("No opener for '{}'", describe(&[path]))));
+    return Ok(());
+  };
+  let mut command = opener.command(&path);
+  match opener.mode {
+    // Waited on another thread, so that it does not remain a zombie
+    OpenMode::Detached => match command.spawn() {
+      Ok(mut child) => drop(thread::spawn(move || child.wait())),
+      Err(e) => app.status = Some(Status::Error(format!("{}: {e}", opener.program()))),
+    },
+    OpenMode::Foreground => {
+      match run_suspended(terminal, &mut command, app.piped, false) {
+        Ok(status) if status.success() => {}
+        Ok(status) => app.status = Some(Status::Error(format!("{}: exited with {status}", opener.program()))),
+        Err(e) => app.status = Some(Status::Error(format!("{}: {e}", opener.program()))),
+      }
+      app.refresh()?;
+    }
+  }
+  Ok(())
+}
+
+/// `$VISUAL`, `$EDITOR` or `vi`
+fn editor() -> String {
+  ["VISUAL", "EDITOR"]
+    .into_iter()
+    .find_map(|name| env::var(name).ok().filter(|editor| !editor.trim().is_empty()))
+    .unwrap_or_else(|| "vi".into())
+}
+
+/// Edit the selected file at the selected line, and come back to the same line
+fn edit<B: Backend + Write>(terminal: &mut Terminal<B>, app: &mut App) -> anyhow::Result<()> {
+  let Some((path, line)) = app.edit_target()? else {
+    return Ok(());
+  };
+  let editor = editor();
+  let mut command = editor_command(&editor, &path, line);
+  match run_suspended(terminal, &mut command, app.piped, false) {
+    Ok(status) if status.success() => {}
+    Ok(status) => app.status = Some(Status::Error(format!("{editor}: exited with {status}"))),
+    Err(e) => app.status = Some(Status::Error(format!("{editor}: {e}"))),
+  }
+  app.reload_contents()
+}
+
+/// Run `command` on the terminal with the TUI suspended.
+/// `piped`: stdout is a pipeline, so the output is shown on the terminal instead
+/// `wait`: the output is left on the screen until a key is pressed
+fn run_suspended<B: Backend + Write>(
+  terminal: &mut Terminal<B>,
+  command: &mut Command,
+  piped: bool,
+  wait: bool,
+) -> Result<ExitStatus, AppError> {
+  if piped {
+    command.stdout(open_tty().map_or_else(|| Stdio::from(io::stderr()), Stdio::from));
+  }
+  suspend_terminal(terminal)?;
+  let status = {
+    #[cfg(unix)]
+    let _ignored = IgnoreInterrupts::new(command);
+    command.status()
+  };
+  let waited = if wait { wait_key(terminal) } else { Ok(()) };
+  resume_terminal(terminal)?;
+  waited?;
+  Ok(status?)
+}
+
+/// Ctrl+C and Ctrl+\ go to the whole foreground process group, so the TUI ignores them until it is dropped,
+/// while the program run in the terminal takes them as it did before.
+#[cfg(unix)]
+struct IgnoreInterrupts {
+  int: libc::sighandler_t,
+  quit: libc::sighandler_t,
+}
+
+#[cfg(unix)]
+impl IgnoreInterrupts {
+  fn new(command: &mut Command) -> Self {
+    use std::os::unix::process::CommandExt;
+    // SAFETY: `signal` is async-signal-safe, which is all the child may call before exec
+    unsafe {
+      let ignored =
+        Self { int: libc::signal(libc::SIGINT, libc::SIG_IGN), quit: libc::signal(libc::SIGQUIT, libc::SIG_IGN) };
+      let (int, quit) = (ignored.int, ignored.quit);
+      command.pre_exec(move || {
+        libc::signal(libc::SIGINT, int);
+        libc::signal(libc::SIGQUIT, quit);
+        Ok(())
+      });
+      ignored
+    }
+  }
+}
+
+#[cfg(unix)]
+impl Drop for IgnoreInterrupts {
+  fn drop(&mut self) {
+    // SAFETY: the handlers are the ones replaced in `new`
+    unsafe {
+      libc::signal(libc::SIGINT, self.int);
+      libc::signal(libc::SIGQUIT, self.quit);
+    }
+  }
+}
+
+fn wait_key<B: Backend + Write>(terminal: &mut Terminal<B>) -> io::Result<()> {
+  write!(terminal.backend_mut(), "\n[Press any key to continue]")?;
+  Write::flush(terminal.backend_mut())?;
+  enable_raw_mode()?;
+  loop {
+    if let Event::Key(key) = event::read()?
+      && key.kind != KeyEventKind::Release
+    {
+      return Ok(());
+    }
+  }
+}
+
+/// Keys while the input line of a file operation or a shell command is open
+fn run_prompt<B: Backend + Write>(terminal: &mut Terminal<B>, app: &mut App, key: KeyEvent) -> anyhow::Result<()> {
+  let Some(prompt) = app.prompt.as_mut() else {
+    return Ok(());
+  };
+  if prompt.kind.is_yes_no() {
+    // Anything but `y` cancels
+    match key.code {
+      KeyCode::Char('y' | 'Y') => app.confirm_prompt()?,
+      _ => app.prompt = None,
+    }
+    return Ok(());
+  }
+  match key.code {
+    // close
+    KeyCode::Char('c') if key.modifiers == KeyModifiers::CONTROL => app.prompt = None,
+    KeyCode::Esc => app.prompt = None,
+
+    KeyCode::Enter if prompt.kind == PromptKind::Shell => {
+      let command = mem::take(&mut prompt.input);
+      app.prompt = None;
+      run_shell(terminal, app, &command)?;
+    }
+    KeyCode::Enter => app.confirm_prompt()?,
+
+    // input
+    KeyCode::Char(c) if (key.modifiers - KeyModifiers::SHIFT).is_empty() => prompt.input.push(c),
+    KeyCode::Backspace => {
+      prompt.input.pop();
+    }
+    KeyCode::Delete => prompt.input.clear(),
+
+    _ => {}
+  }
+  Ok(())
+}
+
+/// Keys while an overlay list such as the history or bookmarks is open
+fn run_picker(app: &mut App, key: KeyEvent) -> anyhow::Result<()> {
+  let Some(picker) = app.picker.as_mut() else {
+    return Ok(());
+  };
+  match key.code {
+    // close
+    KeyCode::Char('c') if key.modifiers == KeyModifiers::CONTROL => app.close_picker()?,
+    KeyCode::Esc => app.close_picker()?,
+
+    // bookmark
+    KeyCode::Char('d') if key.modifiers == KeyModifiers::CONTROL => app.remove_selected_bookmark(),
+
+    // jump
+    KeyCode::Enter => {
+      if let Some(path) = picker.selected_path() {
+        app.jump(path)?;
+      }
+    }
+
+    // move
+    KeyCode::Home => picker.select(0),
+    KeyCode::End => picker.select(picker.list.len().saturating_sub(1)),
+    KeyCode::Down => {
+      picker.next();
+    }
+    KeyCode::Up => {
+      picker.previous();
+    }
+
+    // input
+    KeyCode::Char(c) => {
+      app.search.text.push(c);
+      app.update_picker_filter();
+    }
+    KeyCode::Backspace => {
+      app.search.text.pop();
+      app.update_picker_filter();
+    }
+    KeyCode::Delete => {
+      app.search.text.clear();
+      app.update_picker_filter();
+    }
+
+    _ => {}
+  }
+  Ok(())
+}
@@ -1,11 +1,17 @@
 use ratatui::widgets::ListState;
 
-use super::{ItemInfo, State};
+use super::{ItemInfo, Query, State};
 
 #[derive(Debug)]
 pub struct Search {
   pub text: String,
+  /// `text` parsed, the last valid one while `error` is set
+  pub query: Query,
+  /// Why `text` is not a valid query
+  pub error: Option<String>,
   pub list: Vec<ItemInfo>,
+  /// Score of each item in `list`, to merge in the results found later by the recursive search
+  pub scores: Vec<i64>,
   pub state: ListState,
 }
 
@@ -14,7 +20,7 @@ impl Search {
   pub fn new() -> Self {
     let mut state = ListState::default();
     state.select(Some(0));
-    Search { text: String::new(), list: vec![], state }
+    Search { text: String::new(), query: Query::default(), error: None, list: vec![], scores: vec![], state }
   }
 }
 
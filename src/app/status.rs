@@ -0,0 +1,63 @@
+use std::path::PathBuf;
+
+use super::rename::Rename;
+
+/// Result of the last file operation, shown in the bottom line until the next key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+  Info(String),
+  Error(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PromptKind {
+  Rename(PathBuf),
+  NewFile,
+  NewDir,
+  /// Answered with `y` or `n` instead of the input
+  Delete(Vec<PathBuf>),
+  /// Answered with `y` or `n`, with the renames previewed
+  BulkRename(Vec<Rename>),
+  /// Run in the shell, see `App::expand_command`
+  Shell,
+}
+
+impl PromptKind {
+  /// `y` confirms, and any other key cancels
+  pub fn is_yes_no(&self) -> bool {
+    matches!(self, PromptKind::Delete(_) | PromptKind::BulkRename(_))
+  }
+}
+
+/// Input line in the bottom line, which takes the keys until it is confirmed or canceled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Prompt {
+  pub kind: PromptKind,
+  pub label: String,
+  pub input: String,
+}
+
+impl Prompt {
+  pub fn new(kind: PromptKind, label: String, input: String) -> Self {
+    Prompt { kind, label, input }
+  }
+}
+
+/// The file name of a single path, or the number of paths
+pub fn describe(paths: &[PathBuf]) -> String {
+  match paths {
+    [path] => path.file_name().map(|name| name.to_string_lossy().into()).unwrap_or_else(|| path.display().to_string()),
+    _ => format!("{} items", paths.len()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_describe() {
+    assert_eq!(describe(&[PathBuf::from("/tmp/a.txt")]), "a.txt");
+    assert_eq!(describe(&[PathBuf::from("/tmp/a"), PathBuf::from("/tmp/b")]), "2 items");
+  }
+}
@@ -1,13 +1,16 @@
+use std::path::{Path, PathBuf};
+
 use ratatui::{
   Frame,
-  layout::{Constraint, Direction, Layout},
+  layout::{Constraint, Direction, Flex, Layout, Rect},
   style::{Color, Modifier, Style},
-  text::Span,
-  widgets::{Block, Borders, List},
+  text::{Line, Span},
+  widgets::{Block, Borders, Clear, List},
   widgets::{ListItem, ListState},
 };
 
-use super::{App, AppMode, Item, ItemInfo, ItemPath, item::ItemSymlink};
+use super::grep::find;
+use super::{App, AppMode, Item, ItemInfo, ItemPath, PromptKind, Search, SortMode, Status, item::ItemSymlink};
 use crate::Config;
 
 /* Color
@@ -17,9 +20,15 @@ use crate::Config;
 - dir: blue
 - search: green
 - file, content, none: gray
+- note of the preview: dark gray
+- highlighted content: base16-ocean.dark theme
 - symlink: cyan
 - current-highlight: bold, underlined, bright
 - parent-highlight: magenta
+- search-match: yellow, bold
+- search-error: red
+- mark: light green, bold
+- status: gray, red for errors
 */
 
 struct MyStyle;
@@ -31,6 +40,15 @@ impl MyStyle {
   fn highlight_style() -> Style {
     Style::default().fg(Color::Magenta)
   }
+  fn match_style() -> Style {
+    Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+  }
+  fn mark_style() -> Style {
+    Style::default().fg(Color::LightGreen).add_modifier(Modifier::BOLD)
+  }
+  fn error_style() -> Style {
+    Style::default().fg(Color::Red)
+  }
 }
 
 pub fn ui(f: &mut Frame, app: &mut App) {
@@ -40,9 +58,11 @@ pub fn ui(f: &mut Frame, app: &mut App) {
   }
 
   // layout
+  // The status line takes the bottom line only while there is something to show
+  let status_height = if app.prompt.is_some() || app.status.is_some() { 1 } else { 0 };
   let chunks = Layout::default()
     .direction(Direction::Vertical)
-    .constraints([Constraint::Percentage(10), Constraint::Max(100)])
+    .constraints([Constraint::Percentage(10), Constraint::Max(100), Constraint::Length(status_height)])
     .split(f.area());
 
   // top----------------------------------------------------------
@@ -52,50 +72,97 @@ pub fn ui(f: &mut Frame, app: &mut App) {
     .split(chunks[0]);
 
   // show wd
-  f.render_widget(
-    Block::default().title(Span::styled(app.generate_wd_str(), Style::default().fg(Color::Yellow))),
-    top_chunks[0],
-  );
+  let mut title = vec![Span::styled(app.generate_wd_str(), Style::default().fg(Color::Yellow))];
+  if app.read_options.sort != SortMode::default() || app.read_options.reverse {
+    let reverse = if app.read_options.reverse { " (reverse)" } else { "" };
+    title.push(Span::styled(format!("  sort: {}{reverse}", app.read_options.sort), Style::default().fg(Color::Gray)));
+  }
+  if !app.marks.is_empty() {
+    title.push(Span::styled(format!("  marked: {}", app.marks.len()), MyStyle::mark_style()));
+  }
+  f.render_widget(Block::default().title(Line::from(title)), top_chunks[0]);
 
   // search
-  let item = ItemInfo { item: Item::Search(app.search.text.clone()), index: Some(0) };
+  let found = match (&app.walk, &app.grep) {
+    (Some(walk), _) => Some((walk.found.len(), walk.done)),
+    (_, Some(grep)) => Some((grep.walk.found.len(), grep.walk.done)),
+    _ => None,
+  };
+  let text = match found {
+    Some((len, done)) => format!("{}  ({len}{})", app.search.text, if done { "" } else { "…" }),
+    None => app.search.text.clone(),
+  };
+  let item = ItemInfo { item: Item::Search(text), index: Some(0) };
   let search_items = vec![item];
-  let search_items = set_items(&search_items, app.config);
+  let search_items = match &app.search.error {
+    // An invalid query is shown with the reason
+    Some(e) => vec![ListItem::new(Line::from(vec![
+      Span::styled(app.search.text.clone(), MyStyle::error_style()),
+      Span::styled(format!("  {e}"), MyStyle::error_style()),
+    ]))],
+    None => set_items(&search_items, &app.config, None, None, &[], &[]),
+  };
   let search_text = List::new(search_items).highlight_symbol("> ");
   let mut state = ListState::default();
-  if app.mode == AppMode::Normal {
-    state.select(None);
-  } else if app.mode == AppMode::Search {
+  if app.mode == AppMode::Search || app.picker.is_some() {
     state.select(Some(0));
+  } else {
+    state.select(None);
   }
   f.render_stateful_widget(search_text, top_chunks[1], &mut state);
 
   // bottom------------------------------------------------------
+  let ratios = app.config.column_ratios();
+  let sum = ratios.iter().map(|r| *r as u32).sum();
   let bottom_chunks = Layout::default()
     .direction(Direction::Horizontal)
-    .constraints([
-      Constraint::Percentage(20),
-      Constraint::Percentage(20),
-      Constraint::Max(100),
-      Constraint::Percentage(30),
-    ])
+    .constraints(ratios.iter().map(|r| Constraint::Ratio(*r as u32, sum)))
     .split(chunks[1]);
 
   // grandparent
-  let grandparent_items = set_items(&app.grandparent_items.items, app.config);
+  let grandparent_items = set_items(&app.grandparent_items.items, &app.config, None, None, &app.marks, &[]);
   let grandparent_items =
     List::new(grandparent_items).block(MyStyle::right_border()).highlight_style(MyStyle::highlight_style());
   f.render_stateful_widget(grandparent_items, bottom_chunks[0], &mut app.grandparent_items.state);
 
   // parent
-  let parent_items = set_items(&app.parent_items.items, app.config);
+  let parent_items = set_items(&app.parent_items.items, &app.config, None, None, &app.marks, &[]);
   let parent_items = List::new(parent_items).block(MyStyle::right_border()).highlight_style(MyStyle::highlight_style());
   f.render_stateful_widget(parent_items, bottom_chunks[1], &mut app.parent_items.state);
 
+  // syntax highlighting of the file contents, as far as they are shown
+  let shown_file = if app.config.is_highlight() { app.shown_file() } else { None };
+  if let Some((path, in_working_block)) = &shown_file {
+    let height = chunks[1].height as usize;
+    let (contents, end) = if *in_working_block {
+      let end = match app.judge_mode() {
+        AppMode::Normal => shown_end(&app.items.items, app.items.selected(), height),
+        AppMode::Search => shown_end(&app.search.list, app.search.state.selected().unwrap_or(0), height),
+      };
+      (&app.items.items, end)
+    } else {
+      let end = shown_end(&app.child_items.items, app.child_items.state.selected().unwrap_or(0), height);
+      (&app.child_items.items, end)
+    };
+    app.highlighter.highlight(path, contents, end);
+  }
+  let (working_highlighted, child_highlighted) = match &shown_file {
+    Some((path, true)) => (app.highlighter.lines(path), &[][..]),
+    Some((path, false)) => (&[][..], app.highlighter.lines(path)),
+    None => (&[][..], &[][..]),
+  };
+
   // current
   let (items, state) = match app.judge_mode() {
-    AppMode::Normal => (set_items(&app.items.items, app.config), &mut app.items.state),
-    AppMode::Search => (set_items(&app.search.list, app.config), &mut app.search.state),
+    AppMode::Normal => {
+      (set_items(&app.items.items, &app.config, None, None, &app.marks, working_highlighted), &mut app.items.state)
+    }
+    AppMode::Search => {
+      let root =
+        app.walk.as_ref().map(|walk| walk.root.as_path()).or(app.grep.as_ref().map(|grep| grep.walk.root.as_path()));
+      let items = set_items(&app.search.list, &app.config, Some(&app.search), root, &app.marks, working_highlighted);
+      (items, &mut app.search.state)
+    }
   };
   let items = List::new(items)
     .block(MyStyle::right_border())
@@ -104,37 +171,170 @@ pub fn ui(f: &mut Frame, app: &mut App) {
   f.render_stateful_widget(items, bottom_chunks[2], state);
 
   // child
-  let child_items = set_items(&app.child_items.items, app.config);
+  let child_items = set_items(&app.child_items.items, &app.config, None, None, &app.marks, child_highlighted);
   let child_items = List::new(child_items).highlight_style(MyStyle::highlight_style());
   f.render_stateful_widget(child_items, bottom_chunks[3], &mut app.child_items.state);
+
+  // status
+  if let Some(prompt) = &app.prompt {
+    let line = Line::from(vec![
+      Span::styled(prompt.label.clone(), Style::default().fg(Color::Yellow)),
+      Span::styled(prompt.input.clone(), Style::default().fg(Color::Gray)),
+    ]);
+    let x = chunks[2].x + (line.width() as u16).min(chunks[2].width.saturating_sub(1));
+    f.render_widget(line, chunks[2]);
+    f.set_cursor_position((x, chunks[2].y));
+  } else if let Some(status) = &app.status {
+    let line = match status {
+      Status::Info(message) => Line::styled(message.clone(), Style::default().fg(Color::Gray)),
+      Status::Error(message) => Line::styled(message.clone(), MyStyle::error_style()),
+    };
+    f.render_widget(line, chunks[2]);
+  }
+
+  // overlay
+  if let Some(picker) = app.picker.as_mut() {
+    let area = centered_rect(chunks[1], 60, 60);
+    let items = picker
+      .list
+      .iter()
+      .map(|entry| ListItem::new(Span::styled(entry.label.clone(), Style::default().fg(Color::Blue))))
+      .collect::<Vec<_>>();
+    let items = List::new(items)
+      .block(
+        Block::default()
+          .borders(Borders::ALL)
+          .border_style(Style::default().fg(Color::Gray))
+          .title(Span::styled(picker.kind.title(), Style::default().fg(Color::Yellow))),
+      )
+      .highlight_style(Style::default().add_modifier(Modifier::BOLD).add_modifier(Modifier::UNDERLINED))
+      .highlight_symbol("> ");
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(items, area, &mut picker.state);
+  }
+
+  // preview of the bulk rename
+  if let Some(PromptKind::BulkRename(renames)) = app.prompt.as_ref().map(|prompt| &prompt.kind) {
+    let area = centered_rect(chunks[1], 80, 80);
+    let relative = |path: &Path| path.strip_prefix(&app.wd).unwrap_or(path).to_string_lossy().to_string();
+    let items = renames
+      .iter()
+      .map(|rename| {
+        let mut spans = vec![
+          Span::styled(relative(&rename.from), Style::default().fg(Color::Gray)),
+          Span::styled(" -> ", Style::default().fg(Color::Gray)),
+          Span::styled(relative(&rename.to), Style::default().fg(Color::Blue)),
+        ];
+        if rename.cycle {
+          spans.push(Span::styled("  (cycle)", Style::default().fg(Color::Magenta)));
+        }
+        ListItem::new(Line::from(spans))
+      })
+      .collect::<Vec<_>>();
+    let items = List::new(items).block(
+      Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Gray))
+        .title(Span::styled("Rename", Style::default().fg(Color::Yellow))),
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(items, area);
+  }
+}
+
+/// Lines of file contents up to the last one which can be shown, when `selected` is in the `height` rows
+fn shown_end(items: &[ItemInfo], selected: usize, height: usize) -> usize {
+  items.iter().take(selected + height).filter_map(|item| item.index).max().map_or(0, |i| i + 1)
+}
+
+fn centered_rect(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
+  let [area] = Layout::vertical([Constraint::Percentage(percent_y)]).flex(Flex::Center).areas(area);
+  let [area] = Layout::horizontal([Constraint::Percentage(percent_x)]).flex(Flex::Center).areas(area);
+  area
 }
 
-fn set_items(items: &[ItemInfo], config: Config) -> Vec<ListItem> {
+/// `search`: characters of file names, lines of file contents or grepped lines matched with it are highlighted
+/// `root`: paths are shown relative to it instead of file names
+/// `marks`: items with these paths are marked
+/// `highlighted`: lines of file contents shown with these spans instead
+fn set_items<'a>(
+  items: &'a [ItemInfo],
+  config: &Config,
+  search: Option<&Search>,
+  root: Option<&Path>,
+  marks: &[PathBuf],
+  highlighted: &[Vec<Span<'static>>],
+) -> Vec<ListItem<'a>> {
   items
     .iter()
     .filter_map(|item| {
       let style = match item.item {
-        Item::Content(_) | Item::None | Item::Path(ItemPath::File(_)) => Style::default().fg(Color::Gray),
+        Item::Content(_) | Item::Grep(_) | Item::None | Item::Path(ItemPath::File(_)) => {
+          Style::default().fg(Color::Gray)
+        }
         Item::Path(ItemPath::Dir(_)) => Style::default().fg(Color::Blue),
         Item::Search(_) => Style::default().fg(Color::Green),
         Item::Path(ItemPath::Symlink(ItemSymlink::Dir(_))) => Style::default().fg(Color::Cyan),
         Item::Path(ItemPath::Symlink(ItemSymlink::File(_))) => Style::default().fg(Color::LightCyan),
         Item::Path(ItemPath::Unknown(_)) => Style::default().fg(Color::Red),
+        Item::Note(_) => Style::default().fg(Color::DarkGray),
       };
 
-      let mut text = if let Item::Search(text) = &item.item {
+      let text = if let Item::Search(text) = &item.item {
         text.into()
-      } else if let Item::Content(text) = &item.item {
+      } else if let Item::Content(text) | Item::Note(text) = &item.item {
         text.into()
+      } else if let Item::Grep(m) = &item.item {
+        let path = root.and_then(|root| m.path.strip_prefix(root).ok()).unwrap_or(&m.path);
+        format!("{}:{}: {}", path.display(), m.line + 1, m.text)
+      } else if let Some(root) = root {
+        let path = item.get_path()?;
+        path.strip_prefix(root).unwrap_or(&path).to_string_lossy().into()
       } else {
         item.generate_filename()?
       };
 
-      if config.is_show_index(items) {
-        text = format!("{} {}", item.index.unwrap_or(0) + 1, text);
+      let mut spans = vec![];
+      if item.get_path().is_some_and(|path| marks.contains(&path)) {
+        spans.push(Span::styled("* ", MyStyle::mark_style()));
+      }
+      if config.is_show_index(items)
+        && let Some(index) = item.index
+      {
+        spans.push(Span::styled(format!("{} ", index + 1), style));
+      }
+      let positions = match (&item.item, search) {
+        (Item::Path(_), Some(search)) => search.query.find(&text).map(|m| m.positions),
+        (Item::Content(line), Some(search)) => search.query.find_line(line),
+        // The matched text is at the end of the line
+        (Item::Grep(m), Some(search)) => find(&m.text, &search.text).map(|range| {
+          let offset = text.chars().count() - m.text.chars().count();
+          (range.start + offset..range.end + offset).collect()
+        }),
+        _ => None,
+      };
+      let highlighted = match &item.item {
+        Item::Content(_) => item.index.and_then(|i| highlighted.get(i)),
+        _ => None,
+      };
+      match (positions, highlighted) {
+        (Some(positions), Some(line)) => {
+          // The matched characters are marked on top of the syntax colors
+          let chars = line.iter().flat_map(|span| span.content.chars().map(|c| (c, span.style)));
+          spans.extend(chars.enumerate().map(|(i, (c, style))| {
+            let style = if positions.contains(&i) { style.patch(MyStyle::match_style()) } else { style };
+            Span::styled(c.to_string(), style)
+          }));
+        }
+        (None, Some(line)) => spans.extend(line.iter().cloned()),
+        (Some(positions), None) => spans.extend(text.chars().enumerate().map(|(i, c)| {
+          let style = if positions.contains(&i) { MyStyle::match_style() } else { style };
+          Span::styled(c.to_string(), style)
+        })),
+        (None, None) => spans.push(Span::styled(text, style)),
       }
 
-      Some(ListItem::new(Span::styled(text, style)))
+      Some(ListItem::new(Line::from(spans)))
     })
     .collect()
 }
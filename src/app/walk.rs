@@ -0,0 +1,194 @@
+use std::{
+  fs, mem,
+  path::{Path, PathBuf},
+  sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+    mpsc::{self, Receiver, Sender},
+  },
+  thread,
+};
+
+#[cfg(test)]
+use std::time::{Duration, Instant};
+
+use super::item::is_hidden;
+
+/// Results are sent to the TUI in batches of this size
+const BATCH: usize = 256;
+/// Walking stops after finding this many paths
+const MAX_FOUND: usize = 100_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalkOptions {
+  pub max_depth: usize,
+  pub show_hidden: bool,
+  /// Directory names that are not descended into
+  pub ignore: Vec<String>,
+}
+
+/// Results found below `root` by a background thread
+#[derive(Debug)]
+pub struct Walk<T = PathBuf> {
+  pub root: PathBuf,
+  pub found: Vec<T>,
+  pub done: bool,
+  receiver: Receiver<Vec<T>>,
+  cancel: Arc<AtomicBool>,
+}
+
+impl Walk {
+  /// Find the descendants of `root`
+  pub fn start(root: PathBuf, options: WalkOptions) -> Self {
+    Self::spawn(root, move |root, batcher, cancel| {
+      let mut found = 0;
+      descendants(root, &options, cancel, |entry| {
+        found += 1;
+        batcher.push(entry.path()) && found < MAX_FOUND
+      });
+    })
+  }
+}
+
+impl<T: Send + 'static> Walk<T> {
+  /// Run `f` on a background thread, which should stop once the cancel flag is set
+  pub fn spawn<F>(root: PathBuf, f: F) -> Self
+  where
+    F: FnOnce(&Path, &mut Batcher<T>, &AtomicBool) + Send + 'static,
+  {
+    let (sender, receiver) = mpsc::channel();
+    let cancel = Arc::new(AtomicBool::new(false));
+    let walker_root = root.clone();
+    let walker_cancel = Arc::clone(&cancel);
+    thread::spawn(move || f(&walker_root, &mut Batcher { sender, batch: vec![] }, &walker_cancel));
+    Walk { root, found: vec![], done: false, receiver, cancel }
+  }
+
+  /// Take the results found since the last call, `true` if there were any
+  pub fn receive(&mut self) -> bool {
+    let mut received = false;
+    loop {
+      match self.receiver.try_recv() {
+        Ok(batch) => {
+          self.found.extend(batch);
+          received = true;
+        }
+        Err(mpsc::TryRecvError::Empty) => break,
+        Err(mpsc::TryRecvError::Disconnected) => {
+          self.done = true;
+          break;
+        }
+      }
+    }
+    received
+  }
+}
+
+#[cfg(test)]
+impl<T> Walk<T> {
+  /// Receive until the walk is done, panicking if it takes too long
+  pub fn wait(&mut self) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !self.done {
+      match self.receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+        Ok(batch) => self.found.extend(batch),
+        Err(mpsc::RecvTimeoutError::Disconnected) => self.done = true,
+        Err(mpsc::RecvTimeoutError::Timeout) => panic!("the walk did not finish in time"),
+      }
+    }
+  }
+}
+
+impl<T> Walk<T> {
+  pub fn relative<'a>(&self, path: &'a Path) -> &'a Path {
+    path.strip_prefix(&self.root).unwrap_or(path)
+  }
+}
+
+impl<T> Drop for Walk<T> {
+  fn drop(&mut self) {
+    self.cancel.store(true, Ordering::Relaxed);
+  }
+}
+
+/// Sends results to the TUI in batches, and the rest when dropped
+pub struct Batcher<T> {
+  sender: Sender<Vec<T>>,
+  batch: Vec<T>,
+}
+
+impl<T> Batcher<T> {
+  /// `false` once the TUI no longer receives
+  pub fn push(&mut self, item: T) -> bool {
+    self.batch.push(item);
+    self.batch.len() < BATCH || self.sender.send(mem::take(&mut self.batch)).is_ok()
+  }
+}
+
+impl<T> Drop for Batcher<T> {
+  fn drop(&mut self) {
+    let _ = self.sender.send(mem::take(&mut self.batch));
+  }
+}
+
+/// Visit the descendants of `root` breadth-first, so that shallow paths come first.
+/// Stops when `visit` returns `false` or `cancel` is set.
+pub fn descendants<F>(root: &Path, options: &WalkOptions, cancel: &AtomicBool, mut visit: F)
+where
+  F: FnMut(&fs::DirEntry) -> bool,
+{
+  let mut dirs = vec![root.to_path_buf()];
+  for _ in 0..options.max_depth {
+    let mut next_dirs = vec![];
+    for dir in dirs {
+      let Ok(read_dir) = fs::read_dir(&dir) else {
+        continue;
+      };
+      let mut entries = read_dir.filter_map(|entry| entry.ok()).collect::<Vec<_>>();
+      entries.sort_by_key(|entry| entry.file_name());
+      for entry in entries {
+        if cancel.load(Ordering::Relaxed) {
+          return;
+        }
+        if !options.show_hidden && is_hidden(entry.path()) {
+          continue;
+        }
+        // Symlinks are listed but not followed
+        let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+        if is_dir && options.ignore.iter().any(|name| entry.file_name().to_string_lossy() == name.as_str()) {
+          continue;
+        }
+        if is_dir {
+          next_dirs.push(entry.path());
+        }
+        if !visit(&entry) {
+          return;
+        }
+      }
+    }
+    dirs = next_dirs;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::TempDir;
+
+  #[test]
+  fn test_walk() {
+    let root = TempDir::new("walk");
+    for dir in ["a/b/c/d", "a/.hidden", "target/debug", "e"] {
+      fs::create_dir_all(root.join(dir)).unwrap();
+    }
+    fs::write(root.join("a/b/file.txt"), "").unwrap();
+
+    let options = WalkOptions { max_depth: 3, show_hidden: false, ignore: vec!["target".into()] };
+    let mut walk = Walk::start(root.to_path_buf(), options);
+    walk.wait();
+    let mut found = walk.found.iter().map(|p| walk.relative(p).to_path_buf()).collect::<Vec<_>>();
+    found.sort();
+    let expected = ["a", "a/b", "a/b/c", "a/b/file.txt", "e"].iter().map(PathBuf::from).collect::<Vec<_>>();
+    assert_eq!(found, expected);
+  }
+}
@@ -0,0 +1,130 @@
+use std::{
+  collections::BTreeMap,
+  fs,
+  path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, ConfigError, FileSystemError};
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+pub struct Bookmarks {
+  #[serde(default)]
+  pub bookmarks: BTreeMap<String, PathBuf>,
+}
+
+impl Bookmarks {
+  pub fn output_path() -> Result<PathBuf, AppError> {
+    Ok(crate::config::data_dir()?.join("bookmarks.toml"))
+  }
+
+  pub fn load() -> Result<Self, AppError> {
+    let path = Self::output_path()?;
+    if !path.exists() {
+      return Ok(Self::default());
+    }
+    let s = fs::read_to_string(&path).map_err(FileSystemError::FileReadFailed)?;
+    Ok(toml::from_str(&s).map_err(|e| ConfigError::DataParseFailed(e.to_string()))?)
+  }
+
+  pub fn save(&self) -> Result<(), AppError> {
+    let path = crate::config::create_data_dir()?.join("bookmarks.toml");
+    let s = toml::to_string_pretty(self).map_err(|e| ConfigError::DataParseFailed(e.to_string()))?;
+    fs::write(path, s).map_err(FileSystemError::FileWriteFailed)?;
+    Ok(())
+  }
+
+  /// Add or overwrite the bookmark `name`
+  pub fn add<P: AsRef<Path>>(&mut self, name: &str, path: P) {
+    self.bookmarks.insert(name.into(), path.as_ref().into());
+  }
+
+  /// Bookmark `path` under its directory name, unless it is already bookmarked
+  pub fn add_path<P: AsRef<Path>>(&mut self, path: P) -> String {
+    if let Some(name) = self.find_name(&path) {
+      return name;
+    }
+    let base = path.as_ref().file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_else(|| "/".into());
+    let mut name = base.clone();
+    let mut i = 2;
+    while self.bookmarks.contains_key(&name) {
+      name = format!("{base}-{i}");
+      i += 1;
+    }
+    self.add(&name, path);
+    name
+  }
+
+  pub fn remove(&mut self, name: &str) -> Option<PathBuf> {
+    self.bookmarks.remove(name)
+  }
+
+  pub fn find_name<P: AsRef<Path>>(&self, path: P) -> Option<String> {
+    self.bookmarks.iter().find(|(_, p)| *p == path.as_ref()).map(|(name, _)| name.clone())
+  }
+}
+
+/// Bookmark `path`, or the current directory, as `name`
+pub fn add_bookmark(name: &str, path: Option<&Path>) -> anyhow::Result<()> {
+  let mut bookmarks = Bookmarks::load()?;
+  let path = match path {
+    Some(path) => path.canonicalize()?,
+    None => std::env::current_dir()?,
+  };
+  if !path.is_dir() {
+    return Err(FileSystemError::InvalidPath(path.to_string_lossy().into()).into());
+  }
+  bookmarks.add(name, path);
+  bookmarks.save()?;
+  Ok(())
+}
+
+pub fn list_bookmarks() -> anyhow::Result<()> {
+  for (name, path) in &Bookmarks::load()?.bookmarks {
+    println!("{name}\t{}", path.display());
+  }
+  Ok(())
+}
+
+pub fn remove_bookmark(name: &str) -> anyhow::Result<()> {
+  let mut bookmarks = Bookmarks::load()?;
+  if bookmarks.remove(name).is_none() {
+    anyhow::bail!("Bookmark '{name}' not found");
+  }
+  bookmarks.save()?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_add_path() {
+    let mut bookmarks = Bookmarks::default();
+    assert_eq!(bookmarks.add_path("/work/api"), "api");
+    assert_eq!(bookmarks.add_path("/work/api"), "api");
+    assert_eq!(bookmarks.add_path("/other/api"), "api-2");
+    assert_eq!(bookmarks.add_path("/"), "/");
+    assert_eq!(bookmarks.bookmarks.len(), 3);
+  }
+
+  #[test]
+  fn test_remove() {
+    let mut bookmarks = Bookmarks::default();
+    bookmarks.add("root", "/work");
+    assert_eq!(bookmarks.find_name("/work"), Some("root".into()));
+    assert_eq!(bookmarks.remove("root"), Some(PathBuf::from("/work")));
+    assert_eq!(bookmarks.remove("root"), None);
+  }
+
+  #[test]
+  fn test_serde() {
+    let mut bookmarks = Bookmarks::default();
+    bookmarks.add("root", "/work");
+    let s = toml::to_string_pretty(&bookmarks).unwrap();
+    assert_eq!(toml::from_str::<Bookmarks>(&s).unwrap(), bookmarks);
+    assert_eq!(toml::from_str::<Bookmarks>("").unwrap(), Bookmarks::default());
+  }
+}
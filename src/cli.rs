@@ -1,48 +1,104 @@
-use clap::{Parser, arg, command};
+use std::{
+  ffi::{OsStr, OsString},
+  path::{Path, PathBuf},
+};
 
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+
+use crate::action::{Action, Separator};
+use crate::app::Keymap;
 use crate::error::PrintError;
 use crate::shell::Shell;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about = "Tools for easy cd\nThe `cd` functionality can also be used as-is", long_about = None)]
-#[command(override_usage = "ed\n
- -------------------------------------------------------------
-|   Key         |    Description                              |
-|---------------|---------------------------------------------|
-| ↑ k           | Move up                                     |
-| ↓ j           | Move down                                   |
-| ← h           | Move parent directory                       |
-| → l           | Move Child directory                        |
-| Home          | Move to top                                 |
-| End           | Move to bottom                              |
-| PageUp        | Skip a little and move up                   |
-| PageDown      | Skip a little and move down                 |
-| Enter c ;     | Change directory to current directory       |
-| Esc Ctrl+c q  | Exit and return to original directory       |
-| Insert Ctrl+s | Search mode switch (Char key will not work) |
-| Backspace     | Delete one character from the search string |
-| Delete        | Delete all search strings                   |
-| V             | Open vscode                                 |
-| p             | Print the selected filepath                 |
- -------------------------------------------------------------")]
 pub struct Cli {
   #[arg(short, hide(true))]
   temp_path: Option<String>,
+  #[arg(long, hide(true), conflicts_with_all = ["temp_path", "pick"])]
+  stdout: bool,
+  #[arg(long, hide(true), conflicts_with = "pick")]
+  widget: bool,
   #[arg(long, value_enum, value_name = "SHELL", help = "Configure shell")]
   init: Option<Shell>,
-  #[arg(long, help = "Show all environment variables")]
+  #[arg(
+    long,
+    value_name = "NAME",
+    default_value = "ed",
+    requires = "init",
+    value_parser = parse_cmd,
+    help = "Name of the shell function defined by --init"
+  )]
+  cmd: String,
+  #[arg(long, requires = "init", help = "Also define <NAME>i, which opens the history filtered by the query")]
+  aliases: bool,
+  #[arg(
+    long,
+    value_name = "KEY",
+    requires = "init",
+    value_parser = parse_key,
+    help = "Bind a key such as ctrl-g to open the TUI from the command line (bash, fish, powershell, zsh)"
+  )]
+  bind: Option<char>,
+  #[arg(long, hide(true))]
+  history: Option<String>,
+  #[arg(long, help = "Show all settings and where they come from")]
   env: bool,
+  #[arg(long, help = "Print the marked paths on Enter instead of changing directory")]
+  pick: bool,
+  #[arg(short = '0', long, requires = "pick", help = "Separate the picked paths with NUL instead of newline")]
+  null: bool,
+  #[command(subcommand)]
+  command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+  /// Manage bookmarks
+  Bookmark {
+    #[command(subcommand)]
+    command: BookmarkCommand,
+  },
+  /// Print the most frecent directory in the history matching the query
+  Query { query: String },
+  /// Show or revert the renames and moves made in the TUI
+  Journal {
+    #[command(subcommand)]
+    command: JournalCommand,
+  },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum BookmarkCommand {
+  /// Bookmark a directory (default: current directory)
+  Add { name: String, path: Option<PathBuf> },
+  /// Print all bookmarks
+  List,
+  /// Remove a bookmark
+  Rm { name: String },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum JournalCommand {
+  /// Print the recent changes, newest first
+  List {
+    #[arg(short = 'n', long, default_value_t = 20)]
+    limit: usize,
+  },
+  /// Move the path of a change back, unless something is already at the old path
+  Revert { id: u64 },
 }
 
 impl Cli {
   pub fn match_options(&self) {
     self.match_init();
     self.match_env();
+    self.match_command();
   }
 
   fn match_init(&self) {
     if let Some(shell) = &self.init {
-      if let Err(e) = crate::init(shell) {
+      if let Err(e) = crate::init(shell, &self.cmd, self.aliases, self.bind) {
         e.eprintln();
       }
       std::process::exit(exitcode::OK);
@@ -59,18 +115,110 @@ impl Cli {
     }
   }
 
-  pub fn prepare_cd(&self, cd_path: &std::path::Path) {
-    if let Some(temp_path) = self.temp_path.as_ref() {
-      if let Err(e) = crate::pipe_shell(cd_path, temp_path) {
-        e.eprintln();
-        std::process::exit(exitcode::OK);
+  fn match_command(&self) {
+    if let Some(command) = &self.command {
+      let result = match command {
+        Command::Bookmark { command } => bookmark(command).map(|_| exitcode::OK),
+        Command::Query { query } => crate::history::query(query),
+        Command::Journal { command } => journal(command).map(|_| exitcode::OK),
+      };
+      match result {
+        Ok(code) => std::process::exit(code),
+        Err(e) => {
+          e.eprintln();
+          std::process::exit(exitcode::DATAERR);
+        }
       }
     }
   }
+
+  /// Query to filter the history with on startup
+  pub fn history_query(&self) -> Option<&str> {
+    self.history.as_deref()
+  }
+
+  /// Separator of the picked paths if `--pick` is given
+  pub fn pick(&self) -> Option<Separator> {
+    self.pick.then_some(if self.null { Separator::Nul } else { Separator::Newline })
+  }
+
+  /// If the destination is printed on stdout, which the shell reads, `true`
+  pub fn is_handoff(&self) -> bool {
+    self.stdout
+  }
+
+  /// If opened by the key bound with `--bind`, `true`
+  pub fn is_widget(&self) -> bool {
+    self.widget
+  }
+
+  pub fn prepare_cd(&self, cd_path: &Path) {
+    self.hand_off(cd_path.as_os_str());
+  }
+
+  /// `cd PATH` to change directory, or `insert PATH` to insert the path into the command line.
+  /// Nothing for the other actions.
+  pub fn prepare_widget(&self, action: &Action) {
+    let (verb, path) = match action {
+      Action::Change(path) => ("cd ", path),
+      Action::Print(path) => ("insert ", path),
+      Action::Keep | Action::PrintMany(..) => return,
+    };
+    let mut text = OsString::from(verb);
+    text.push(path);
+    self.hand_off(&text);
+  }
+
+  fn hand_off(&self, text: &OsStr) {
+    let result = match self.temp_path.as_ref() {
+      _ if self.stdout => crate::print_path(text),
+      Some(temp_path) => crate::pipe_shell(text, temp_path),
+      None => Ok(()),
+    };
+    if let Err(e) = result {
+      e.eprintln();
+      std::process::exit(exitcode::OK);
+    }
+  }
+}
+
+/// A name every shell takes for a function
+fn parse_cmd(name: &str) -> Result<String, String> {
+  let mut chars = name.chars();
+  let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+    && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+  if valid { Ok(name.into()) } else { Err("letters, digits, '_' and '-', not starting with a digit or '-'".into()) }
+}
+
+fn bookmark(command: &BookmarkCommand) -> anyhow::Result<()> {
+  match command {
+    BookmarkCommand::Add { name, path } => crate::bookmark::add_bookmark(name, path.as_deref()),
+    BookmarkCommand::List => crate::bookmark::list_bookmarks(),
+    BookmarkCommand::Rm { name } => crate::bookmark::remove_bookmark(name),
+  }
+}
+
+fn journal(command: &JournalCommand) -> anyhow::Result<()> {
+  match command {
+    JournalCommand::List { limit } => crate::journal::list_changes(*limit),
+    JournalCommand::Revert { id } => crate::journal::revert_change(*id),
+  }
+}
+
+/// `ctrl-g`, the letter pressed with Ctrl
+fn parse_key(key: &str) -> Result<char, String> {
+  let letter = key.to_ascii_lowercase().strip_prefix("ctrl-").and_then(|letter| {
+    let mut chars = letter.chars();
+    chars.next().filter(|c| c.is_ascii_lowercase() && chars.next().is_none())
+  });
+  letter.ok_or_else(|| "Ctrl and a letter, such as ctrl-g".into())
 }
 
+/// The key table in the usage is generated from the active keymap
 pub fn cli() -> Cli {
-  Cli::parse()
+  let usage = crate::Config::new().map(|c| c.keymap().usage()).unwrap_or_else(|_| Keymap::default().usage());
+  let matches = Cli::command().override_usage(usage).get_matches();
+  Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit())
 }
 
 #[cfg(test)]
@@ -79,7 +227,74 @@ mod tests {
 
   #[test]
   fn verify_cli() {
-    use clap::CommandFactory;
-    Cli::command().debug_assert();
+    Cli::command().override_usage(Keymap::default().usage()).debug_assert();
+  }
+
+  #[test]
+  fn test_bookmark_command() {
+    let cli = Cli::parse_from(["easychangedirectory", "bookmark", "add", "root", "/work"]);
+    assert!(matches!(
+      cli.command,
+      Some(Command::Bookmark { command: BookmarkCommand::Add { name, path: Some(_) } }) if name == "root"
+    ));
+    let cli = Cli::parse_from(["easychangedirectory", "bookmark", "rm", "root"]);
+    assert!(matches!(cli.command, Some(Command::Bookmark { command: BookmarkCommand::Rm { .. } })));
+  }
+
+  #[test]
+  fn test_query_command() {
+    let cli = Cli::parse_from(["easychangedirectory", "query", "api"]);
+    assert!(matches!(cli.command, Some(Command::Query { query }) if query == "api"));
+    let cli = Cli::parse_from(["easychangedirectory", "-t", "/tmp/a", "--history", "api"]);
+    assert_eq!(cli.history_query(), Some("api"));
+  }
+
+  #[test]
+  fn test_init_cmd() {
+    let cli = Cli::parse_from(["easychangedirectory", "--init", "bash"]);
+    assert_eq!((cli.cmd.as_str(), cli.aliases), ("ed", false));
+    let cli = Cli::parse_from(["easychangedirectory", "--init", "zsh", "--cmd", "cdi", "--aliases"]);
+    assert_eq!((cli.cmd.as_str(), cli.aliases), ("cdi", true));
+    assert!(Cli::try_parse_from(["easychangedirectory", "--init", "zsh", "--cmd", "1ed"]).is_err());
+    assert!(Cli::try_parse_from(["easychangedirectory", "--init", "zsh", "--cmd", "e d"]).is_err());
+    assert!(Cli::try_parse_from(["easychangedirectory", "--cmd", "cdi"]).is_err());
+  }
+
+  #[test]
+  fn test_handoff() {
+    let cli = Cli::parse_from(["easychangedirectory", "--stdout", "--history", "api"]);
+    assert!(cli.is_handoff());
+    assert!(!Cli::parse_from(["easychangedirectory", "-t", "/tmp/a"]).is_handoff());
+    assert!(Cli::try_parse_from(["easychangedirectory", "--stdout", "-t", "/tmp/a"]).is_err());
+    assert!(Cli::try_parse_from(["easychangedirectory", "--stdout", "--pick"]).is_err());
+  }
+
+  #[test]
+  fn test_bind() {
+    let cli = Cli::parse_from(["easychangedirectory", "--init", "zsh", "--bind", "Ctrl-G"]);
+    assert_eq!(cli.bind, Some('g'));
+    assert!(Cli::try_parse_from(["easychangedirectory", "--init", "zsh", "--bind", "alt-g"]).is_err());
+    assert!(Cli::try_parse_from(["easychangedirectory", "--init", "zsh", "--bind", "ctrl-gg"]).is_err());
+    let cli = Cli::parse_from(["easychangedirectory", "--widget", "--stdout"]);
+    assert!(cli.is_widget() && cli.is_handoff());
+  }
+
+  #[test]
+  fn test_journal_command() {
+    let cli = Cli::parse_from(["easychangedirectory", "journal", "list"]);
+    assert!(matches!(cli.command, Some(Command::Journal { command: JournalCommand::List { limit: 20 } })));
+    let cli = Cli::parse_from(["easychangedirectory", "journal", "revert", "3"]);
+    assert!(matches!(cli.command, Some(Command::Journal { command: JournalCommand::Revert { id: 3 } })));
+  }
+
+  #[test]
+  fn test_pick() {
+    let cli = Cli::parse_from(["easychangedirectory"]);
+    assert_eq!(cli.pick(), None);
+    let cli = Cli::parse_from(["easychangedirectory", "--pick"]);
+    assert_eq!(cli.pick(), Some(Separator::Newline));
+    let cli = Cli::parse_from(["easychangedirectory", "--pick", "-0"]);
+    assert_eq!(cli.pick(), Some(Separator::Nul));
+    assert!(Cli::try_parse_from(["easychangedirectory", "--null"]).is_err());
   }
 }
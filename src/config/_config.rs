@@ -0,0 +1,284 @@
+use std::{fmt, path::Path};
+
+use super::env::{EnvConfig, flag};
+use super::file::FileConfig;
+use crate::app::{Item, ItemInfo, Keymap, Opener, Openers, ReadOptions, SortMode, WalkOptions};
+use crate::error::{AppError, ConfigError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+  Default,
+  File,
+  Env,
+}
+
+impl fmt::Display for Source {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Source::Default => write!(f, "default"),
+      Source::File => write!(f, "config file"),
+      Source::Env => write!(f, "environment variable"),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Setting<T> {
+  pub value: T,
+  pub source: Source,
+}
+
+impl<T> Setting<T> {
+  /// The environment variable takes precedence over the config file
+  fn resolve(default: T, file: Option<T>, env: Option<T>) -> Self {
+    match (file, env) {
+      (_, Some(value)) => Setting { value, source: Source::Env },
+      (Some(value), None) => Setting { value, source: Source::File },
+      (None, None) => Setting { value: default, source: Source::Default },
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+  pwd: Setting<bool>,
+  set_bg: Setting<bool>,
+  show_index: Setting<bool>,
+  view_file_contents: Setting<bool>,
+  log: Setting<bool>,
+  show_hidden: Setting<bool>,
+  sort: Setting<SortMode>,
+  sort_reverse: Setting<bool>,
+  jump: Setting<usize>,
+  column_ratios: Setting<Vec<u16>>,
+  editor: Setting<String>,
+  search_depth: Setting<usize>,
+  search_ignore: Setting<Vec<String>>,
+  grep_max_size: Setting<u64>,
+  trash: Setting<bool>,
+  highlight: Setting<bool>,
+  keymap: Setting<Keymap>,
+  openers: Setting<Openers>,
+}
+
+impl Default for Config {
+  fn default() -> Self {
+    Self::resolve(FileConfig::default(), EnvConfig::default()).expect("default config is valid")
+  }
+}
+
+impl Config {
+  pub fn new() -> anyhow::Result<Self> {
+    Ok(Self::resolve(FileConfig::load()?, EnvConfig::new()?)?)
+  }
+
+  fn resolve(file: FileConfig, env: EnvConfig) -> Result<Self, AppError> {
+    let config = Config {
+      pwd: Setting::resolve(false, file.pwd, flag(env._ed_pwd)),
+      set_bg: Setting::resolve(false, file.set_bg, flag(env._ed_set_bg)),
+      show_index: Setting::resolve(false, file.show_index, flag(env._ed_show_index)),
+      view_file_contents: Setting::resolve(false, file.view_file_contents, flag(env._ed_view_file_contents)),
+      log: Setting::resolve(false, file.log, flag(env._ed_log)),
+      show_hidden: Setting::resolve(true, file.show_hidden, flag(env._ed_show_hidden)),
+      sort: Setting::resolve(SortMode::default(), file.sort, env._ed_sort),
+      sort_reverse: Setting::resolve(false, file.sort_reverse, flag(env._ed_sort_reverse)),
+      jump: Setting::resolve(4, file.jump, env._ed_jump),
+      column_ratios: Setting::resolve(vec![20, 20, 30, 30], file.column_ratios, env._ed_column_ratios),
+      editor: Setting::resolve("code".into(), file.editor, env._ed_editor),
+      search_depth: Setting::resolve(8, file.search_depth, env._ed_search_depth),
+      search_ignore: Setting::resolve(
+        vec![".git".into(), "target".into(), "node_modules".into()],
+        file.search_ignore,
+        env._ed_search_ignore,
+      ),
+      grep_max_size: Setting::resolve(1024 * 1024, file.grep_max_size, env._ed_grep_max_size),
+      trash: Setting::resolve(true, file.trash, flag(env._ed_trash)),
+      highlight: Setting::resolve(true, file.highlight, flag(env._ed_highlight)),
+      keymap: Setting::resolve(Keymap::default(), file.keys.as_ref().map(Keymap::with_config).transpose()?, None),
+      openers: Setting::resolve(
+        Openers::default(),
+        file.openers.as_deref().map(Openers::with_config).transpose()?,
+        None,
+      ),
+    };
+
+    if config.column_ratios.value.len() != 4 || config.column_ratios.value.iter().all(|r| *r == 0) {
+      return Err(ConfigError::InvalidValue("column_ratios must be 4 numbers, not all 0".into()).into());
+    }
+    if config.editor.value.trim().is_empty() {
+      return Err(ConfigError::InvalidValue("editor must not be empty".into()).into());
+    }
+
+    Ok(config)
+  }
+
+  pub fn is_pwd(&self) -> bool {
+    self.pwd.value
+  }
+  pub fn is_show_index(&self, items: &[ItemInfo]) -> bool {
+    self.show_index.value && !items.is_empty() && !matches!(items[0].item, Item::Search(_))
+  }
+  pub fn is_view_file_contents(&self) -> bool {
+    self.view_file_contents.value
+  }
+  pub fn is_set_bg(&self) -> bool {
+    self.set_bg.value
+  }
+  pub fn is_log(&self) -> bool {
+    self.log.value
+  }
+  /// Initial way of listing directories, which can be changed at runtime
+  pub fn read_options(&self) -> ReadOptions {
+    ReadOptions { show_hidden: self.show_hidden.value, sort: self.sort.value, reverse: self.sort_reverse.value }
+  }
+  /// Number of items skipped by `PageUp` and `PageDown`
+  pub fn jump(&self) -> usize {
+    self.jump.value
+  }
+  /// Width ratios of grandparent, parent, current and child columns
+  pub fn column_ratios(&self) -> &[u16] {
+    &self.column_ratios.value
+  }
+  /// Command and arguments to open the current directory with
+  pub fn editor(&self) -> Vec<&str> {
+    self.editor.value.split_whitespace().collect()
+  }
+  /// The first opener matching `path`. Directories without one are opened with `editor`.
+  pub fn opener(&self, path: &Path) -> Option<Opener> {
+    let is_dir = path.is_dir();
+    match self.openers.value.find(path, is_dir) {
+      Some(opener) => Some(opener.clone()),
+      None if is_dir => Opener::dir(&self.editor.value).ok(),
+      None => None,
+    }
+  }
+
+  /// How the recursive search walks below the working directory
+  pub fn walk_options(&self, show_hidden: bool) -> WalkOptions {
+    WalkOptions { max_depth: self.search_depth.value, show_hidden, ignore: self.search_ignore.value.clone() }
+  }
+
+  /// Files larger than this many bytes are not grepped
+  pub fn grep_max_size(&self) -> u64 {
+    self.grep_max_size.value
+  }
+
+  /// Deleted items are moved to the trash instead of being removed permanently
+  pub fn is_trash(&self) -> bool {
+    self.trash.value
+  }
+
+  /// File contents are highlighted by their syntax
+  pub fn is_highlight(&self) -> bool {
+    self.highlight.value
+  }
+
+  pub fn keymap(&self) -> &Keymap {
+    &self.keymap.value
+  }
+
+  pub fn show_all(&self) {
+    match FileConfig::path() {
+      Ok(path) => println!("Config file: {}", path.display()),
+      Err(e) => println!("Config file: {e}"),
+    }
+    println!();
+    show("_ED_PWD", "pwd", &self.pwd);
+    show("_ED_SET_BG", "set_bg", &self.set_bg);
+    show("_ED_SHOW_INDEX", "show_index", &self.show_index);
+    show("_ED_VIEW_FILE_CONTENTS", "view_file_contents", &self.view_file_contents);
+    show("_ED_LOG", "log", &self.log);
+    show("_ED_SHOW_HIDDEN", "show_hidden", &self.show_hidden);
+    show("_ED_SORT", "sort", &self.sort);
+    show("_ED_SORT_REVERSE", "sort_reverse", &self.sort_reverse);
+    show("_ED_JUMP", "jump", &self.jump);
+    show(
+      "_ED_COLUMN_RATIOS",
+      "column_ratios",
+      &Setting {
+        value: self.column_ratios.value.iter().map(|r| r.to_string()).collect::<Vec<_>>().join(","),
+        source: self.column_ratios.source,
+      },
+    );
+    show("_ED_EDITOR", "editor", &self.editor);
+    show("_ED_SEARCH_DEPTH", "search_depth", &self.search_depth);
+    show(
+      "_ED_SEARCH_IGNORE",
+      "search_ignore",
+      &Setting { value: self.search_ignore.value.join(","), source: self.search_ignore.source },
+    );
+    show("_ED_GREP_MAX_SIZE", "grep_max_size", &self.grep_max_size);
+    show("_ED_TRASH", "trash", &self.trash);
+    show("_ED_HIGHLIGHT", "highlight", &self.highlight);
+    println!("{:<24}{:<20}= {:<12}({})", "", "keys", "", self.keymap.source);
+    println!("{:<24}{:<20}= {:<12}({})", "", "openers", "", self.openers.source);
+  }
+}
+
+fn show<T: fmt::Display>(env_name: &str, file_key: &str, setting: &Setting<T>) {
+  println!("{env_name:<24}{file_key:<20}= {:<12}({})", setting.value.to_string(), setting.source);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn verify_config() {
+    let env = EnvConfig {
+      _ed_pwd: Some(1),
+      _ed_set_bg: Some(1),
+      _ed_show_index: Some(1),
+      _ed_view_file_contents: Some(1),
+      _ed_log: Some(1),
+      ..Default::default()
+    };
+    let config = Config::resolve(FileConfig::default(), env).unwrap();
+    assert!(config.is_pwd());
+    assert!(config.is_show_index(&[ItemInfo::default()]));
+    assert!(config.is_view_file_contents());
+    assert!(config.is_set_bg());
+    assert!(config.is_log());
+  }
+
+  #[test]
+  fn test_default() {
+    let config = Config::default();
+    assert!(!config.is_pwd());
+    assert_eq!(config.read_options(), ReadOptions::default());
+    assert_eq!(config.jump(), 4);
+    assert_eq!(config.column_ratios(), &[20, 20, 30, 30]);
+    assert_eq!(config.editor(), vec!["code"]);
+    assert_eq!(config.walk_options(true).max_depth, 8);
+    assert_eq!(config.walk_options(true).ignore, [".git", "target", "node_modules"]);
+    assert_eq!(config.grep_max_size(), 1024 * 1024);
+    assert!(config.is_trash());
+    assert!(config.is_highlight());
+    assert_eq!(config.pwd.source, Source::Default);
+    assert_eq!(config.keymap(), &Keymap::default());
+    assert_eq!(config.opener(&std::env::temp_dir()).unwrap().program(), "code");
+    assert!(config.opener(Path::new("Cargo.toml")).is_none());
+  }
+
+  #[test]
+  fn test_env_overrides_file() {
+    let file =
+      FileConfig::parse("pwd = true\nset_bg = true\njump = 8\neditor = \"code -n\"\nsort = \"mtime\"").unwrap();
+    let env = EnvConfig { _ed_pwd: Some(0), _ed_jump: Some(2), _ed_sort_reverse: Some(1), ..Default::default() };
+    let config = Config::resolve(file, env).unwrap();
+    assert_eq!(config.pwd, Setting { value: false, source: Source::Env });
+    assert_eq!(config.set_bg, Setting { value: true, source: Source::File });
+    assert_eq!(config.jump, Setting { value: 2, source: Source::Env });
+    assert_eq!(config.editor(), vec!["code", "-n"]);
+    assert_eq!(config.sort, Setting { value: SortMode::Mtime, source: Source::File });
+    assert!(config.read_options().reverse);
+  }
+
+  #[test]
+  fn test_invalid_value() {
+    let file = FileConfig::parse("column_ratios = [1, 2]").unwrap();
+    assert!(Config::resolve(file, EnvConfig::default()).is_err());
+    let env = EnvConfig { _ed_editor: Some(" ".into()), ..Default::default() };
+    assert!(Config::resolve(FileConfig::default(), env).is_err());
+  }
+}
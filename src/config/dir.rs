@@ -0,0 +1,28 @@
+use std::path::PathBuf;
+
+use crate::error::{AppError, ConfigError};
+
+/// `HOME/.easychangedirectory`, where the log and other persistent data are stored
+pub fn data_dir() -> Result<PathBuf, AppError> {
+  let home_dir = home::home_dir().ok_or(ConfigError::HomeDirectoryNotFound)?;
+  Ok(home_dir.join(format!(".{}", env!("CARGO_PKG_NAME"))))
+}
+
+pub fn create_data_dir() -> Result<PathBuf, AppError> {
+  let dir = data_dir()?;
+  if !dir.exists() {
+    std::fs::create_dir_all(&dir).map_err(ConfigError::DataDirectoryCreationFailed)?;
+  }
+  Ok(dir)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_data_dir() {
+    let expected = home::home_dir().unwrap().join(".easychangedirectory");
+    assert_eq!(data_dir().unwrap(), expected);
+  }
+}
@@ -1,44 +1,37 @@
 use serde::Deserialize;
 
-use crate::app::{Item, ItemInfo};
+use crate::app::SortMode;
 
-#[derive(Deserialize, Debug, Clone, Copy)]
-pub struct Config {
-  _ed_pwd: Option<u8>,
-  _ed_set_bg: Option<u8>,
-  _ed_show_index: Option<u8>,
-  _ed_view_file_contents: Option<u8>,
-  _ed_log: Option<u8>,
+/// Settings given by `_ED_*` environment variables, which override the config file
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct EnvConfig {
+  pub _ed_pwd: Option<u8>,
+  pub _ed_set_bg: Option<u8>,
+  pub _ed_show_index: Option<u8>,
+  pub _ed_view_file_contents: Option<u8>,
+  pub _ed_log: Option<u8>,
+  pub _ed_show_hidden: Option<u8>,
+  pub _ed_sort: Option<SortMode>,
+  pub _ed_sort_reverse: Option<u8>,
+  pub _ed_jump: Option<usize>,
+  pub _ed_column_ratios: Option<Vec<u16>>,
+  pub _ed_editor: Option<String>,
+  pub _ed_search_depth: Option<usize>,
+  pub _ed_search_ignore: Option<Vec<String>>,
+  pub _ed_grep_max_size: Option<u64>,
+  pub _ed_trash: Option<u8>,
+  pub _ed_highlight: Option<u8>,
 }
 
-impl Config {
+impl EnvConfig {
   pub fn new() -> anyhow::Result<Self> {
     Ok(envy::from_env::<Self>()?)
   }
+}
 
-  pub fn is_pwd(&self) -> bool {
-    self._ed_pwd.eq(&Some(1))
-  }
-  pub fn is_show_index(&self, items: &[ItemInfo]) -> bool {
-    self._ed_show_index.eq(&Some(1)) && !items.is_empty() && !matches!(items[0].item, Item::Search(_))
-  }
-  pub fn is_view_file_contents(&self) -> bool {
-    self._ed_view_file_contents.eq(&Some(1))
-  }
-  pub fn is_set_bg(&self) -> bool {
-    self._ed_set_bg.eq(&Some(1))
-  }
-  pub fn is_log(&self) -> bool {
-    self._ed_log.eq(&Some(1))
-  }
-
-  pub fn show_all(&self) {
-    println!("_ED_PWD = {}", self._ed_pwd.map(|u| u.to_string()).unwrap_or_default());
-    println!("_ED_SET_BG = {}", self._ed_set_bg.map(|u| u.to_string()).unwrap_or_default());
-    println!("_ED_SHOW_INDEX = {}", self._ed_show_index.map(|u| u.to_string()).unwrap_or_default());
-    println!("_ED_VIEW_FILE_CONTENTS = {}", self._ed_view_file_contents.map(|u| u.to_string()).unwrap_or_default());
-    println!("_ED_LOG = {}", self._ed_log.map(|u| u.to_string()).unwrap_or_default());
-  }
+/// Only `1` is regarded as enabled
+pub fn flag(value: Option<u8>) -> Option<bool> {
+  value.map(|u| u == 1)
 }
 
 #[cfg(test)]
@@ -46,18 +39,10 @@ mod tests {
   use super::*;
 
   #[test]
-  fn verify_config() {
-    let config = Config {
-      _ed_pwd: Some(1),
-      _ed_set_bg: Some(1),
-      _ed_show_index: Some(1),
-      _ed_view_file_contents: Some(1),
-      _ed_log: Some(1),
-    };
-    assert!(config.is_pwd());
-    assert!(config.is_show_index(&[ItemInfo::default()]));
-    assert!(config.is_view_file_contents());
-    assert!(config.is_set_bg());
-    assert!(config.is_log());
+  fn test_flag() {
+    assert_eq!(flag(Some(1)), Some(true));
+    assert_eq!(flag(Some(0)), Some(false));
+    assert_eq!(flag(Some(2)), Some(false));
+    assert_eq!(flag(None), None);
   }
 }
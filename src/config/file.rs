@@ -0,0 +1,91 @@
+use std::{fs, path::PathBuf};
+
+use serde::Deserialize;
+
+use crate::app::{KeyConfig, OpenerConfig, SortMode};
+use crate::error::{AppError, ConfigError, FileSystemError};
+
+/// Settings given by `config.toml`
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct FileConfig {
+  pub pwd: Option<bool>,
+  pub set_bg: Option<bool>,
+  pub show_index: Option<bool>,
+  pub view_file_contents: Option<bool>,
+  pub log: Option<bool>,
+  pub show_hidden: Option<bool>,
+  pub sort: Option<SortMode>,
+  pub sort_reverse: Option<bool>,
+  pub jump: Option<usize>,
+  pub column_ratios: Option<Vec<u16>>,
+  pub editor: Option<String>,
+  pub search_depth: Option<usize>,
+  pub search_ignore: Option<Vec<String>>,
+  pub grep_max_size: Option<u64>,
+  pub trash: Option<bool>,
+  pub highlight: Option<bool>,
+  pub keys: Option<KeyConfig>,
+  pub openers: Option<Vec<OpenerConfig>>,
+}
+
+impl FileConfig {
+  /// `XDG_CONFIG_HOME/easychangedirectory/config.toml`, or `HOME/.config/easychangedirectory/config.toml`
+  pub fn path() -> Result<PathBuf, AppError> {
+    let config_dir = match std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+      Some(dir) => PathBuf::from(dir),
+      None => home::home_dir().ok_or(ConfigError::HomeDirectoryNotFound)?.join(".config"),
+    };
+    Ok(config_dir.join(env!("CARGO_PKG_NAME")).join("config.toml"))
+  }
+
+  /// A missing file is the same as an empty one
+  pub fn load() -> Result<Self, AppError> {
+    let path = Self::path()?;
+    if !path.exists() {
+      return Ok(Self::default());
+    }
+    let s = fs::read_to_string(&path).map_err(FileSystemError::FileReadFailed)?;
+    Self::parse(&s)
+  }
+
+  pub fn parse(s: &str) -> Result<Self, AppError> {
+    Ok(toml::from_str(s).map_err(|e| ConfigError::ConfigFileParseFailed(e.to_string()))?)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::app::OpenMode;
+
+  #[test]
+  fn test_parse() {
+    let config = FileConfig::parse("pwd = true\njump = 8\ncolumn_ratios = [1, 1, 2, 2]\neditor = \"nvim\"").unwrap();
+    assert_eq!(config.pwd, Some(true));
+    assert_eq!(config.set_bg, None);
+    assert_eq!(config.jump, Some(8));
+    assert_eq!(config.column_ratios, Some(vec![1, 1, 2, 2]));
+    assert_eq!(config.editor, Some("nvim".into()));
+    assert!(config.keys.is_none());
+
+    let config = FileConfig::parse("search_depth = 3\nsearch_ignore = [\".git\"]").unwrap();
+    assert_eq!(config.search_depth, Some(3));
+    assert_eq!(config.search_ignore, Some(vec![".git".into()]));
+
+    let config = FileConfig::parse("[keys.normal]\n\"ctrl+n\" = \"move_next\"").unwrap();
+    assert_eq!(config.keys.unwrap().normal.len(), 1);
+
+    let config =
+      FileConfig::parse("[[openers]]\npattern = \"*.md\"\ncommand = \"glow\"\nmode = \"foreground\"").unwrap();
+    assert_eq!(config.openers.unwrap()[0].mode, OpenMode::Foreground);
+  }
+
+  #[test]
+  fn test_parse_error() {
+    assert!(FileConfig::parse("pwd = 1").is_err());
+    assert!(FileConfig::parse("unknown = true").is_err());
+    assert!(FileConfig::parse("[keys.normal]\nj = \"unknown\"").is_err());
+    assert!(FileConfig::parse("[[openers]]\npattern = \"*.md\"\ncommand = \"glow\"\nmode = \"tab\"").is_err());
+  }
+}
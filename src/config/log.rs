@@ -11,17 +11,16 @@ pub struct Log;
 
 impl Log {
   pub fn output_path() -> Result<PathBuf, AppError> {
-    let home_dir = home::home_dir().ok_or(ConfigError::HomeDirectoryNotFound)?;
-    Ok(home_dir.join(format!(".{}", env!("CARGO_PKG_NAME"))).join("ed.log"))
+    Ok(super::data_dir()?.join("ed.log"))
   }
 
   pub fn init() -> Result<(), AppError> {
     let path = Self::output_path()?;
 
-    if let Some(parent) = path.parent() {
-      if !parent.exists() {
-        std::fs::create_dir_all(parent).map_err(ConfigError::LogDirectoryCreationFailed)?;
-      }
+    if let Some(parent) = path.parent()
+      && !parent.exists()
+    {
+      std::fs::create_dir_all(parent).map_err(ConfigError::LogDirectoryCreationFailed)?;
     }
 
     let log_file = File::create(&path).map_err(ConfigError::LogFileCreationFailed)?;
@@ -1,5 +1,9 @@
+mod _config;
+mod dir;
 mod env;
+mod file;
 mod log;
 
-pub use self::env::Config;
+pub use self::_config::Config;
+pub use self::dir::{create_data_dir, data_dir};
 pub use self::log::Log;
@@ -1,30 +1,66 @@
-use std::{fs::File, io::Write, path::Path};
+use std::{
+  ffi::OsStr,
+  fs::OpenOptions,
+  io::{self, Write},
+};
 
-pub fn pipe_shell(path: &Path, temp_path: &str) -> anyhow::Result<()> {
-  let mut f = File::create(temp_path)?;
+/// Write the destination to `temp_path` for the shell to read.
+/// The file is only created if it does not exist yet, and only the user can read it,
+/// so that a link or file left in the shared temp directory by someone else is never written through.
+pub fn pipe_shell(path: &OsStr, temp_path: &str) -> anyhow::Result<()> {
+  let mut options = OpenOptions::new();
+  options.write(true).create_new(true);
+  #[cfg(unix)]
+  std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+  let mut f = options.open(temp_path).map_err(|e| match e.kind() {
+    // Left by a shell function of 0.8.0 or earlier, which never removes it
+    io::ErrorKind::AlreadyExists => {
+      anyhow::anyhow!("{temp_path} already exists, register the shell function again with --init after upgrading")
+    }
+    _ => e.into(),
+  })?;
   f.write_all(path.to_string_lossy().as_bytes())?;
 
   Ok(())
 }
 
+/// Print the destination on stdout for `cd "$(easychangedirectory --stdout)"`, as it is even if it is not UTF-8
+pub fn print_path(path: &OsStr) -> anyhow::Result<()> {
+  let mut stdout = io::stdout().lock();
+  stdout.write_all(path.as_encoded_bytes())?;
+  stdout.flush()?;
+
+  Ok(())
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::testing::TempDir;
   use std::fs;
   use std::path::PathBuf;
 
   #[test]
   fn test_pipe_shell() {
     let test_path = PathBuf::from("/tmp");
-    let temp_path = "/tmp/test_pipe_shell";
+    let dir = TempDir::new("pipe_shell");
+    let temp_path = dir.join("out");
+    let temp_path = temp_path.to_str().unwrap();
 
-    let result = pipe_shell(&test_path, temp_path);
+    let result = pipe_shell(test_path.as_os_str(), temp_path);
     assert!(result.is_ok());
 
     let content = fs::read_to_string(temp_path).expect("Failed to read temp file in test");
     assert_eq!(content, "/tmp");
 
-    // クリーンアップ
-    let _ = fs::remove_file(temp_path);
+    // 既にあるファイルには書き込まない
+    let error = pipe_shell(test_path.as_os_str(), temp_path).unwrap_err();
+    assert!(error.to_string().contains("register the shell function again"));
+    #[cfg(unix)]
+    {
+      use std::os::unix::fs::PermissionsExt;
+      let mode = fs::metadata(temp_path).unwrap().permissions().mode();
+      assert_eq!(mode & 0o777, 0o600);
+    }
   }
 }
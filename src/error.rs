@@ -15,6 +15,10 @@ pub enum ConfigError {
   LogDirectoryCreationFailed(std::io::Error),
   LogFileCreationFailed(std::io::Error),
   LogInitializationFailed(String),
+  DataDirectoryCreationFailed(std::io::Error),
+  DataParseFailed(String),
+  ConfigFileParseFailed(String),
+  InvalidValue(String),
 }
 
 #[derive(Debug)]
@@ -23,6 +27,14 @@ pub enum FileSystemError {
   PermissionDenied(String),
   InvalidPath(String),
   DirectoryReadFailed(std::io::Error),
+  FileReadFailed(std::io::Error),
+  FileWriteFailed(std::io::Error),
+  AlreadyExists(String),
+  CopyFailed(String, std::io::Error),
+  MoveFailed(String, std::io::Error),
+  RemoveFailed(String, std::io::Error),
+  CreationFailed(String, std::io::Error),
+  DuplicateTarget(String),
 }
 
 #[derive(Debug)]
@@ -51,6 +63,10 @@ impl fmt::Display for ConfigError {
       ConfigError::LogDirectoryCreationFailed(e) => write!(f, "Failed to create log directory: {e}"),
       ConfigError::LogFileCreationFailed(e) => write!(f, "Failed to create log file: {e}"),
       ConfigError::LogInitializationFailed(msg) => write!(f, "Failed to initialize logging: {msg}"),
+      ConfigError::DataDirectoryCreationFailed(e) => write!(f, "Failed to create data directory: {e}"),
+      ConfigError::DataParseFailed(msg) => write!(f, "Failed to parse data file: {msg}"),
+      ConfigError::ConfigFileParseFailed(msg) => write!(f, "Failed to parse config file: {msg}"),
+      ConfigError::InvalidValue(msg) => write!(f, "Invalid value: {msg}"),
     }
   }
 }
@@ -62,6 +78,14 @@ impl fmt::Display for FileSystemError {
       FileSystemError::PermissionDenied(path) => write!(f, "Permission denied for path '{path}'"),
       FileSystemError::InvalidPath(path) => write!(f, "Invalid path: '{path}'"),
       FileSystemError::DirectoryReadFailed(e) => write!(f, "Failed to read directory: {e}"),
+      FileSystemError::FileReadFailed(e) => write!(f, "Failed to read file: {e}"),
+      FileSystemError::FileWriteFailed(e) => write!(f, "Failed to write file: {e}"),
+      FileSystemError::AlreadyExists(path) => write!(f, "Path '{path}' already exists"),
+      FileSystemError::CopyFailed(path, e) => write!(f, "Failed to copy '{path}': {e}"),
+      FileSystemError::MoveFailed(path, e) => write!(f, "Failed to move '{path}': {e}"),
+      FileSystemError::RemoveFailed(path, e) => write!(f, "Failed to remove '{path}': {e}"),
+      FileSystemError::CreationFailed(path, e) => write!(f, "Failed to create '{path}': {e}"),
+      FileSystemError::DuplicateTarget(path) => write!(f, "More than one item would be renamed to '{path}'"),
     }
   }
 }
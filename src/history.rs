@@ -0,0 +1,207 @@
+use std::{
+  fs,
+  path::{Path, PathBuf},
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::app::is_case_sensitive;
+use crate::error::{AppError, ConfigError, FileSystemError};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Visit {
+  pub path: PathBuf,
+  pub count: u32,
+  /// Unix time in seconds
+  pub last_visited: u64,
+}
+
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+
+impl Visit {
+  /// Visit count weighted by how recently the directory was visited
+  pub fn frecency(&self, now: u64) -> f64 {
+    let age = now.saturating_sub(self.last_visited);
+    let weight = if age < HOUR {
+      4.0
+    } else if age < DAY {
+      2.0
+    } else if age < WEEK {
+      0.5
+    } else {
+      0.25
+    };
+    self.count as f64 * weight
+  }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum QueryResult {
+  Found(PathBuf),
+  Ambiguous,
+  NotFound,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct History {
+  pub visits: Vec<Visit>,
+}
+
+impl History {
+  pub fn output_path() -> Result<PathBuf, AppError> {
+    Ok(crate::config::data_dir()?.join("history.json"))
+  }
+
+  pub fn load() -> Result<Self, AppError> {
+    let path = Self::output_path()?;
+    if !path.exists() {
+      return Ok(Self::default());
+    }
+    let s = fs::read_to_string(&path).map_err(FileSystemError::FileReadFailed)?;
+    Ok(serde_json::from_str(&s).map_err(|e| ConfigError::DataParseFailed(e.to_string()))?)
+  }
+
+  pub fn save(&self) -> Result<(), AppError> {
+    let path = crate::config::create_data_dir()?.join("history.json");
+    let s = serde_json::to_string_pretty(self).map_err(|e| ConfigError::DataParseFailed(e.to_string()))?;
+    fs::write(path, s).map_err(FileSystemError::FileWriteFailed)?;
+    Ok(())
+  }
+
+  pub fn query_path(query: &str) -> Result<QueryResult, AppError> {
+    let mut history = Self::load()?;
+    history.visits.retain(|visit| visit.path.is_dir());
+    Ok(history.query(query, now()))
+  }
+
+  /// Load the history file, add a visit to `path` and save it
+  pub fn record<P: AsRef<Path>>(path: P) -> Result<(), AppError> {
+    let mut history = Self::load()?;
+    history.add(path, now());
+    history.save()
+  }
+
+  pub fn add<P: AsRef<Path>>(&mut self, path: P, timestamp: u64) {
+    match self.visits.iter_mut().find(|v| v.path == path.as_ref()) {
+      Some(visit) => {
+        visit.count += 1;
+        visit.last_visited = timestamp;
+      }
+      None => self.visits.push(Visit { path: path.as_ref().into(), count: 1, last_visited: timestamp }),
+    }
+  }
+
+  /// Most recently visited first
+  pub fn recent(&self) -> Vec<Visit> {
+    let mut visits = self.visits.clone();
+    visits.sort_by_key(|v| std::cmp::Reverse(v.last_visited));
+    visits
+  }
+
+  /// Best scoring directory whose path contains `query` (smart-case).
+  /// Directories whose name matches are preferred over those where only a parent matches.
+  /// The result is ambiguous unless the best score is at least twice the runner-up.
+  pub fn query(&self, query: &str, now: u64) -> QueryResult {
+    let (name_matches, path_matches): (Vec<_>, Vec<_>) = self
+      .visits
+      .iter()
+      .filter(|visit| is_match(&visit.path.to_string_lossy(), query))
+      .partition(|visit| visit.path.file_name().is_some_and(|name| is_match(&name.to_string_lossy(), query)));
+    let mut candidates = if name_matches.is_empty() { path_matches } else { name_matches };
+    candidates.sort_by(|a, b| b.frecency(now).total_cmp(&a.frecency(now)));
+
+    match candidates.as_slice() {
+      [] => QueryResult::NotFound,
+      [best] => QueryResult::Found(best.path.clone()),
+      [best, second, ..] if best.frecency(now) >= second.frecency(now) * 2.0 => QueryResult::Found(best.path.clone()),
+      _ => QueryResult::Ambiguous,
+    }
+  }
+}
+
+/// Exit status of `query` when the TUI should be opened instead, referred to by the shell scripts
+pub const AMBIGUOUS: exitcode::ExitCode = 2;
+
+/// Print the best matching directory and record the visit
+pub fn query(query: &str) -> anyhow::Result<exitcode::ExitCode> {
+  match History::query_path(query)? {
+    QueryResult::Found(path) => {
+      println!("{}", path.display());
+      History::record(&path)?;
+      Ok(exitcode::OK)
+    }
+    QueryResult::Ambiguous => Ok(AMBIGUOUS),
+    QueryResult::NotFound => anyhow::bail!("No directory in the history matches '{query}'"),
+  }
+}
+
+/// `query` is contained in `s`, in smart-case
+pub fn is_match(s: &str, query: &str) -> bool {
+  if is_case_sensitive(query) { s.contains(query) } else { s.to_lowercase().contains(query) }
+}
+
+pub fn now() -> u64 {
+  SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_add() {
+    let mut history = History::default();
+    history.add("/tmp", 1);
+    history.add("/usr", 2);
+    history.add("/tmp", 3);
+    assert_eq!(history.visits.len(), 2);
+    assert_eq!(history.visits[0], Visit { path: "/tmp".into(), count: 2, last_visited: 3 });
+    assert_eq!(history.visits[1], Visit { path: "/usr".into(), count: 1, last_visited: 2 });
+  }
+
+  #[test]
+  fn test_recent() {
+    let mut history = History::default();
+    history.add("/a", 3);
+    history.add("/b", 5);
+    history.add("/c", 1);
+    let paths = history.recent().into_iter().map(|v| v.path).collect::<Vec<_>>();
+    assert_eq!(paths, vec![PathBuf::from("/b"), PathBuf::from("/a"), PathBuf::from("/c")]);
+  }
+
+  #[test]
+  fn test_frecency() {
+    let visit = Visit { path: "/tmp".into(), count: 4, last_visited: 0 };
+    assert_eq!(visit.frecency(10), 16.0);
+    assert_eq!(visit.frecency(DAY - 1), 8.0);
+    assert_eq!(visit.frecency(WEEK - 1), 2.0);
+    assert_eq!(visit.frecency(WEEK), 1.0);
+  }
+
+  #[test]
+  fn test_query() {
+    let mut history = History::default();
+    history.add("/work/api", 100);
+    history.add("/work/api", 100);
+    history.add("/work/api/src", 100);
+    history.add("/work/web", 100);
+    history.add("/work/Web2", 100);
+    assert_eq!(history.query("api", 100), QueryResult::Found("/work/api".into()));
+    assert_eq!(history.query("src", 100), QueryResult::Found("/work/api/src".into()));
+    assert_eq!(history.query("web", 100), QueryResult::Ambiguous);
+    assert_eq!(history.query("Web", 100), QueryResult::Found("/work/Web2".into()));
+    assert_eq!(history.query("nothing", 100), QueryResult::NotFound);
+  }
+
+  #[test]
+  fn test_serde() {
+    let mut history = History::default();
+    history.add("/tmp", 10);
+    let s = serde_json::to_string(&history).unwrap();
+    let history: History = serde_json::from_str(&s).unwrap();
+    assert_eq!(history.visits, vec![Visit { path: "/tmp".into(), count: 1, last_visited: 10 }]);
+  }
+}
@@ -1,44 +1,180 @@
 use std::env::temp_dir;
 
+use anyhow::bail;
 use handlebars::Handlebars;
 use serde_json::json;
 
 use crate::shell::Shell;
 
-pub fn init(shell: &Shell) -> anyhow::Result<()> {
-  let shellscript = Handlebars::new()
-    .render_template(shell.get_template(), &json!({ "temp_path": temp_dir().join("_easychangedirectory.txt") }))?;
-
-  println!("{shellscript}");
+/// `cmd`: name of the function
+/// `aliases`: also define `<cmd>i`, which opens the history filtered by the query
+/// `bind`: Ctrl with this letter opens the TUI from the line editor
+pub fn init(shell: &Shell, cmd: &str, aliases: bool, bind: Option<char>) -> anyhow::Result<()> {
+  println!("{}", shellscript(shell, cmd, aliases, bind)?);
 
   Ok(())
 }
 
+fn shellscript(shell: &Shell, cmd: &str, aliases: bool, bind: Option<char>) -> anyhow::Result<String> {
+  if bind.is_some() && !shell.has_widget() {
+    bail!("--bind is not supported for {}", format!("{shell:?}").to_lowercase());
+  }
+  let context = json!({
+    "temp_path": temp_dir().join("_easychangedirectory.txt"),
+    "cmd": cmd,
+    "aliases": aliases,
+    "bind": bind.map(key_names),
+  });
+  Ok(Handlebars::new().render_template(shell.get_template(), &context)?)
+}
+
+/// Ctrl with `key` in the notation of each line editor
+fn key_names(key: char) -> serde_json::Value {
+  json!({
+    "bash": format!("\\C-{key}"),
+    "fish": format!("\\c{key}"),
+    "powershell": format!("Ctrl+{key}"),
+    "zsh": format!("^{}", key.to_ascii_uppercase()),
+  })
+}
+
 #[cfg(test)]
 mod tests {
+  use std::{fs, process::Command};
+
   use super::*;
+  use crate::testing::TempDir;
+
+  /// Parse the script with `program` without running it, which fails if it is not installed.
+  /// `{}` in `args` is replaced with the path of the script.
+  fn parse_check(shell: Shell, program: &str, args: &[&str]) {
+    let dir = TempDir::new(&format!("init_{program}"));
+    let path = dir.join("script");
+    fs::write(&path, shellscript(&shell, "ed", true, shell.has_widget().then_some('g')).unwrap()).unwrap();
+    let output = Command::new(program).args(args.iter().map(|arg| arg.replace("{}", &path.to_string_lossy()))).output();
+    let output = output.unwrap_or_else(|e| panic!("{program}: {e}"));
+    assert!(output.status.success(), "{program}: {}", String::from_utf8_lossy(&output.stderr));
+  }
+
+  #[test]
+  fn test_cmd() {
+    let script = shellscript(&Shell::Bash, "cdi", true, None).unwrap();
+    assert!(script.contains("function cdi() {") && script.contains("function cdii() {"));
+    let script = shellscript(&Shell::Elvish, "j", false, None).unwrap();
+    assert!(script.contains("fn j {|@args|") && script.contains("edit:add-var j~ $j~"));
+    assert!(!script.contains("ji"));
+    let script = shellscript(&Shell::Tcsh, "j", true, None).unwrap();
+    assert!(script.contains("alias j '") && script.contains("alias ji '"));
+    assert!(script.contains(r#"set ed_flag = `expr "x$ed_args[1]" : "x--*[a-zA-Z0-9]"`"#));
+  }
+
+  #[test]
+  fn test_bind() {
+    let script = shellscript(&Shell::Zsh, "ed", true, None).unwrap();
+    assert!(!script.contains("bindkey"));
+    let script = shellscript(&Shell::Zsh, "ed", true, Some('g')).unwrap();
+    assert!(script.contains("bindkey '^G' _ed_widget"));
+    let script = shellscript(&Shell::Bash, "j", true, Some('o')).unwrap();
+    assert!(script.contains(r#"bind -x '"\C-o": _j_widget'"#));
+    assert!(shellscript(&Shell::Nushell, "ed", true, Some('g')).is_err());
+  }
 
   #[test]
   fn test_init_bash() {
-    let result = init(&Shell::Bash);
+    let result = init(&Shell::Bash, "ed", true, None);
+    assert!(result.is_ok());
+  }
+
+  #[test]
+  fn test_parse_bash() {
+    parse_check(Shell::Bash, "bash", &["-n", "{}"]);
+  }
+
+  #[test]
+  fn test_init_elvish() {
+    let result = init(&Shell::Elvish, "ed", true, None);
     assert!(result.is_ok());
   }
 
+  #[test]
+  #[ignore = "needs elvish, run in CI"]
+  fn test_parse_elvish() {
+    parse_check(Shell::Elvish, "elvish", &["-compileonly", "{}"]);
+  }
+
   #[test]
   fn test_init_fish() {
-    let result = init(&Shell::Fish);
+    let result = init(&Shell::Fish, "ed", true, None);
     assert!(result.is_ok());
   }
 
+  #[test]
+  #[ignore = "needs fish, run in CI"]
+  fn test_parse_fish() {
+    parse_check(Shell::Fish, "fish", &["--no-execute", "{}"]);
+  }
+
+  #[test]
+  fn test_init_nushell() {
+    let result = init(&Shell::Nushell, "ed", true, None);
+    assert!(result.is_ok());
+  }
+
+  #[test]
+  #[ignore = "needs nu, run in CI"]
+  fn test_parse_nushell() {
+    parse_check(Shell::Nushell, "nu", &["--no-config-file", "--commands", "if not (nu-check '{}') { exit 1 }"]);
+  }
+
+  #[test]
+  fn test_init_tcsh() {
+    let result = init(&Shell::Tcsh, "ed", true, None);
+    assert!(result.is_ok());
+  }
+
+  #[test]
+  #[ignore = "needs tcsh, run in CI"]
+  fn test_parse_tcsh() {
+    parse_check(Shell::Tcsh, "tcsh", &["-f", "-n", "{}"]);
+  }
+
+  #[test]
+  fn test_init_xonsh() {
+    let result = init(&Shell::Xonsh, "ed", true, None);
+    assert!(result.is_ok());
+  }
+
+  #[test]
+  #[ignore = "needs xonsh, run in CI"]
+  fn test_parse_xonsh() {
+    parse_check(Shell::Xonsh, "xonsh", &["--no-rc", "-c", "compilex(open('{}').read())"]);
+  }
+
   #[test]
   fn test_init_zsh() {
-    let result = init(&Shell::Zsh);
+    let result = init(&Shell::Zsh, "ed", true, None);
     assert!(result.is_ok());
   }
 
+  #[test]
+  #[ignore = "needs zsh, run in CI"]
+  fn test_parse_zsh() {
+    parse_check(Shell::Zsh, "zsh", &["-n", "{}"]);
+  }
+
   #[test]
   fn test_init_powershell() {
-    let result = init(&Shell::Powershell);
+    let result = init(&Shell::Powershell, "ed", true, None);
     assert!(result.is_ok());
   }
+
+  #[test]
+  #[ignore = "needs pwsh, run in CI"]
+  fn test_parse_powershell() {
+    parse_check(
+      Shell::Powershell,
+      "pwsh",
+      &["-NoProfile", "-Command", "$null = [ScriptBlock]::Create((Get-Content -Raw '{}'))"],
+    );
+  }
 }
@@ -0,0 +1,176 @@
+use std::{
+  fmt, fs,
+  path::{Path, PathBuf},
+  time::{Duration, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::app::{move_to, utc_date_time};
+use crate::error::{AppError, ConfigError, FileSystemError};
+use crate::history::now;
+
+/// Entries kept at most, the oldest are dropped
+const MAX_ENTRIES: usize = 1000;
+
+/// A rename or move made in the TUI, reverted by moving `to` back to `from`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct JournalEntry {
+  pub id: u64,
+  pub from: PathBuf,
+  pub to: PathBuf,
+  /// Unix time in seconds
+  pub timestamp: u64,
+  #[serde(default)]
+  pub reverted: bool,
+}
+
+impl fmt::Display for JournalEntry {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let time = utc_date_time(UNIX_EPOCH + Duration::from_secs(self.timestamp));
+    write!(f, "{:>4}  {time}  {} -> {}", self.id, self.from.display(), self.to.display())?;
+    if self.reverted {
+      write!(f, "  (reverted)")?;
+    }
+    Ok(())
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Journal {
+  pub entries: Vec<JournalEntry>,
+}
+
+impl Journal {
+  pub fn output_path() -> Result<PathBuf, AppError> {
+    Ok(crate::config::data_dir()?.join("journal.json"))
+  }
+
+  pub fn load(path: &Path) -> Result<Self, AppError> {
+    if !path.exists() {
+      return Ok(Self::default());
+    }
+    let s = fs::read_to_string(path).map_err(FileSystemError::FileReadFailed)?;
+    Ok(serde_json::from_str(&s).map_err(|e| ConfigError::DataParseFailed(e.to_string()))?)
+  }
+
+  pub fn save(&self, path: &Path) -> Result<(), AppError> {
+    if let Some(dir) = path.parent() {
+      fs::create_dir_all(dir).map_err(ConfigError::DataDirectoryCreationFailed)?;
+    }
+    let s = serde_json::to_string_pretty(self).map_err(|e| ConfigError::DataParseFailed(e.to_string()))?;
+    fs::write(path, s).map_err(FileSystemError::FileWriteFailed)?;
+    Ok(())
+  }
+
+  /// Load the journal at `path`, add a change and save it, returns the id of the change
+  pub fn record(path: &Path, from: &Path, to: &Path) -> Result<u64, AppError> {
+    let mut journal = Self::load(path)?;
+    let id = journal.add(from, to, now());
+    journal.save(path)?;
+    Ok(id)
+  }
+
+  /// Load the journal at `path`, revert the change `id` and save it
+  pub fn revert_saved(path: &Path, id: u64) -> Result<JournalEntry, AppError> {
+    let mut journal = Self::load(path)?;
+    let entry = journal.revert(id)?;
+    journal.save(path)?;
+    Ok(entry)
+  }
+
+  pub fn add(&mut self, from: &Path, to: &Path, timestamp: u64) -> u64 {
+    let id = self.entries.iter().map(|entry| entry.id).max().unwrap_or(0) + 1;
+    self.entries.push(JournalEntry { id, from: from.into(), to: to.into(), timestamp, reverted: false });
+    if self.entries.len() > MAX_ENTRIES {
+      self.entries.drain(..self.entries.len() - MAX_ENTRIES);
+    }
+    id
+  }
+
+  /// Move the path of the change `id` back, without overwriting anything at the old path
+  pub fn revert(&mut self, id: u64) -> Result<JournalEntry, AppError> {
+    let entry = self.find_unreverted(id)?;
+    move_to(&entry.to, &entry.from)?;
+    entry.reverted = true;
+    Ok(entry.clone())
+  }
+
+  /// Load the journal at `path` and mark the changes `ids` as reverted, once they are moved back by the caller
+  pub fn set_reverted_saved(path: &Path, ids: &[u64]) -> Result<(), AppError> {
+    let mut journal = Self::load(path)?;
+    for id in ids {
+      journal.find_unreverted(*id)?.reverted = true;
+    }
+    journal.save(path)
+  }
+
+  /// The change `id`, which is not reverted yet
+  pub fn find_unreverted(&mut self, id: u64) -> Result<&mut JournalEntry, AppError> {
+    let entry = self
+      .entries
+      .iter_mut()
+      .find(|entry| entry.id == id)
+      .ok_or_else(|| AppError::Other(format!("No change with id {id} in the journal")))?;
+    if entry.reverted {
+      return Err(AppError::Other(format!("Change {id} is already reverted")));
+    }
+    Ok(entry)
+  }
+
+  /// Most recent first
+  pub fn recent(&self, limit: usize) -> impl Iterator<Item = &JournalEntry> {
+    self.entries.iter().rev().take(limit)
+  }
+}
+
+/// Print the `limit` most recent changes
+pub fn list_changes(limit: usize) -> anyhow::Result<()> {
+  for entry in Journal::load(&Journal::output_path()?)?.recent(limit) {
+    println!("{entry}");
+  }
+  Ok(())
+}
+
+/// Move the path of the change `id` back
+pub fn revert_change(id: u64) -> anyhow::Result<()> {
+  let entry = Journal::revert_saved(&Journal::output_path()?, id)?;
+  println!("{} -> {}", entry.to.display(), entry.from.display());
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::TempDir;
+
+  #[test]
+  fn test_add() {
+    let mut journal = Journal::default();
+    assert_eq!(journal.add(Path::new("/a"), Path::new("/b"), 1), 1);
+    assert_eq!(journal.add(Path::new("/b"), Path::new("/c"), 2), 2);
+    let ids = journal.recent(10).map(|entry| entry.id).collect::<Vec<_>>();
+    assert_eq!(ids, [2, 1]);
+    assert_eq!(journal.recent(10).next().unwrap().to_string(), "   2  1970-01-01T00:00:02  /b -> /c");
+  }
+
+  #[test]
+  fn test_revert() {
+    let dir = TempDir::new("journal");
+    let path = dir.join("journal.json");
+    fs::write(dir.join("b"), "").unwrap();
+    let id = Journal::record(&path, &dir.join("a"), &dir.join("b")).unwrap();
+
+    // Nothing is overwritten
+    fs::write(dir.join("a"), "").unwrap();
+    let result = Journal::revert_saved(&path, id);
+    assert!(matches!(result, Err(AppError::FileSystem(FileSystemError::AlreadyExists(_)))));
+
+    fs::remove_file(dir.join("a")).unwrap();
+    let entry = Journal::revert_saved(&path, id).unwrap();
+    assert!(entry.reverted);
+    assert!(dir.join("a").exists() && !dir.join("b").exists());
+    assert!(Journal::revert_saved(&path, id).is_err());
+    assert!(Journal::load(&path).unwrap().entries[0].reverted);
+  }
+}
@@ -2,16 +2,21 @@
 
 mod action;
 mod app;
+mod bookmark;
 mod cli;
 mod config;
 mod connect;
 pub mod error;
+mod history;
 mod init;
+mod journal;
 mod shell;
+#[cfg(test)]
+mod testing;
 
 pub use crate::app::app;
 pub use crate::cli::cli;
 pub use crate::config::Config;
 pub use crate::config::Log;
-pub use crate::connect::pipe_shell;
+pub use crate::connect::{pipe_shell, print_path};
 pub use crate::init::init;
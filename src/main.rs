@@ -1,3 +1,5 @@
+use std::io::{self, Write};
+
 use easychangedirectory as ed;
 
 use ed::error::PrintError;
@@ -7,7 +9,7 @@ fn main() {
 
   cli.match_options();
 
-  let action = match ed::app() {
+  let action = match ed::app(cli.history_query(), cli.pick(), cli.is_handoff()) {
     Ok(action) => action,
     Err(e) => {
       e.eprintln();
@@ -15,18 +17,30 @@ fn main() {
     }
   };
 
-  let action_path = action.execute();
+  if cli.is_widget() {
+    cli.prepare_widget(&action);
+    return;
+  }
+
+  // stdout is only for the destination with `--stdout`
+  let mut out: Box<dyn Write> = if cli.is_handoff() { Box::new(io::stderr()) } else { Box::new(io::stdout()) };
+  let action_path = action.execute(&mut out);
+
+  // Nothing else is printed to the pipeline
+  if cli.pick().is_some() {
+    return;
+  }
 
   cli.prepare_cd(&action_path);
 
   if let Ok(config) = ed::Config::new() {
     if config.is_pwd() {
-      println!("Now: {}", action_path.display());
+      let _ = writeln!(out, "Now: {}", action_path.display());
     }
-    if config.is_log() {
-      if let Ok(log_path) = ed::Log::output_path() {
-        println!("Log output location: {}", log_path.display());
-      }
+    if config.is_log()
+      && let Ok(log_path) = ed::Log::output_path()
+    {
+      let _ = writeln!(out, "Log output location: {}", log_path.display());
     }
   }
 }
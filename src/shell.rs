@@ -3,8 +3,12 @@ use clap::ValueEnum;
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 pub enum Shell {
   Bash,
+  Elvish,
   Fish,
+  Nushell,
   Powershell,
+  Tcsh,
+  Xonsh,
   Zsh,
 }
 
@@ -12,92 +16,420 @@ impl Shell {
   pub fn get_template(&self) -> &str {
     match self {
       Shell::Bash => BASH,
+      Shell::Elvish => ELVISH,
       Shell::Fish => FISH,
+      Shell::Nushell => NUSHELL,
       Shell::Powershell => POWERSHELL,
+      Shell::Tcsh => TCSH,
+      Shell::Xonsh => XONSH,
       Shell::Zsh => ZSH,
     }
   }
+
+  /// If `--bind` can bind a key to open the TUI from the line editor
+  pub fn has_widget(&self) -> bool {
+    matches!(self, Shell::Bash | Shell::Fish | Shell::Powershell | Shell::Zsh)
+  }
 }
 
 pub const BASH: &str = r#"
 # # easychangedirectory
 # eval "$(easychangedirectory --init bash)"
 
-function ed() {
+function {{ cmd }}() {
   if [[ "$#" -eq 0 ]]; then
-    temp_path="{{ temp_path }}.$$"
-    easychangedirectory -t "${temp_path}"
-    cd_path=`cat "${temp_path}"`
-    cd "${cd_path}" || return
-  elif [[ "$#" -eq 1 ]] && [[ "$1" =~ ^-+[a-zA-Z]+ ]]; then
-    easychangedirectory "$1"
-  elif [[ "$#" -eq 1 ]]; then
+    cd_path="$(easychangedirectory --stdout)" && [[ -n "${cd_path}" ]] && cd "${cd_path}"
+  elif [[ "$1" =~ ^-+[a-zA-Z0-9]+ ]]; then
+    easychangedirectory "$@"
+  elif [[ "$#" -eq 1 ]] && { [[ -d "$1" ]] || [[ "$1" == "-" ]]; }; then
     cd "$1" || return
+  elif [[ "$#" -eq 1 ]]; then
+    cd_path="$(easychangedirectory query "$1")"
+    case "$?" in
+      0) cd "${cd_path}" || return ;;
+      2)
+        cd_path="$(easychangedirectory --stdout --history "$1")" && [[ -n "${cd_path}" ]] && cd "${cd_path}"
+        ;;
+      *) return 1 ;;
+    esac
   else
     echo 'Too many arguments'
   fi
 }
+{{#if aliases}}
+
+function {{ cmd }}i() {
+  cd_path="$(easychangedirectory --stdout --history "$*")" && [[ -n "${cd_path}" ]] && cd "${cd_path}"
+}
+{{/if}}
+{{#if bind}}
+
+# `{{ bind.bash }}` opens the TUI from the command line, `p` inserts the selected path at the cursor.
+# The prompt shows the new directory from the next command.
+function _{{ cmd }}_widget() {
+  local out quoted
+  out="$(easychangedirectory --widget --stdout < /dev/tty)"
+  case "${out}" in
+    cd\ *) builtin cd -- "${out#cd }" || return ;;
+    insert\ *)
+      quoted="$(printf '%q' "${out#insert }")"
+      READLINE_LINE="${READLINE_LINE:0:READLINE_POINT}${quoted}${READLINE_LINE:READLINE_POINT}"
+      READLINE_POINT=$((READLINE_POINT + ${#quoted}))
+      ;;
+  esac
+}
+bind -x '"{{ bind.bash }}": _{{ cmd }}_widget'
+{{/if}}
+"#;
+
+pub const ELVISH: &str = r#"
+# # easychangedirectory
+# eval (easychangedirectory --init elvish | slurp)
+
+use path
+use re
+use str
+
+# Elvish has no `cd -`
+var ed-oldpwd = $pwd
+set before-chdir = [$@before-chdir {|_| set ed-oldpwd = $pwd }]
+
+fn {{ cmd }} {|@args|
+  if (== (count $args) 0) {
+    var cd-path = (easychangedirectory --stdout | slurp)
+    if (!=s $cd-path '') { cd $cd-path }
+  } elif (re:match '^-+[a-zA-Z0-9]+' $args[0]) {
+    easychangedirectory $@args
+  } elif (and (== (count $args) 1) (eq $args[0] '-')) {
+    cd $ed-oldpwd
+  } elif (and (== (count $args) 1) (path:is-dir $args[0])) {
+    cd $args[0]
+  } elif (== (count $args) 1) {
+    var code = 0
+    var cd-path = ''
+    try {
+      set cd-path = (easychangedirectory query $args[0])
+    } catch e {
+      set code = $e[reason][exit-status]
+    }
+    if (== $code 0) {
+      cd $cd-path
+    } elif (== $code 2) {
+      set cd-path = (easychangedirectory --stdout --history $args[0] | slurp)
+      if (!=s $cd-path '') { cd $cd-path }
+    } else {
+      fail 'easychangedirectory query failed'
+    }
+  } else {
+    echo 'Too many arguments'
+  }
+}
+{{#if aliases}}
+
+fn {{ cmd }}i {|@query|
+  var cd-path = (easychangedirectory --stdout --history (str:join ' ' $query) | slurp)
+  if (!=s $cd-path '') { cd $cd-path }
+}
+{{/if}}
+
+# Definitions in `eval` are not seen by the prompt otherwise
+edit:add-var {{ cmd }}~ ${{ cmd }}~
+{{#if aliases}}
+edit:add-var {{ cmd }}i~ ${{ cmd }}i~
+{{/if}}
 "#;
 
 pub const FISH: &str = r#"
 # # easychangedirectory
 # easychangedirectory --init fish | source
 
-function ed
+function {{ cmd }}
   set arg_cnt (count $argv)
   if test "$arg_cnt" -eq 0
-    set temp_path "{{ temp_path }}.$fish_pid"
-    easychangedirectory -t "$temp_path"
-    set cd_path (cat "$temp_path")
-    cd "$cd_path"
-  else if string match -r '^x\-+[a-zA-Z]+' "x$argv[1]" &> /dev/null
-    easychangedirectory "$argv[1]"
-  else if test "$arg_cnt" -eq 1
+    set cd_path (easychangedirectory --stdout); and test -n "$cd_path"; and cd "$cd_path"
+  else if string match -r '^x\-+[a-zA-Z0-9]+' "x$argv[1]" &> /dev/null
+    easychangedirectory $argv
+  else if test "$arg_cnt" -eq 1; and begin; test -d "$argv[1]"; or test "$argv[1]" = "-"; end
     cd "$argv[1]"
+  else if test "$arg_cnt" -eq 1
+    set cd_path (easychangedirectory query "$argv[1]")
+    switch $status
+      case 0
+        cd "$cd_path"
+      case 2
+        set cd_path (easychangedirectory --stdout --history "$argv[1]"); and test -n "$cd_path"; and cd "$cd_path"
+      case '*'
+        return 1
+    end
   else
     echo 'Too many arguments'
   end
 end
+{{#if aliases}}
+
+function {{ cmd }}i
+  set cd_path (easychangedirectory --stdout --history "$argv"); and test -n "$cd_path"; and cd "$cd_path"
+end
+{{/if}}
+{{#if bind}}
+
+# `{{ bind.fish }}` opens the TUI from the command line, `p` inserts the selected path at the cursor
+function _{{ cmd }}_widget
+  set -l out (easychangedirectory --widget --stdout)
+  switch "$out"
+    case 'cd *'
+      cd (string sub -s 4 -- "$out")
+    case 'insert *'
+      commandline -i -- (string escape -- (string sub -s 8 -- "$out"))
+  end
+  commandline -f repaint
+end
+bind {{ bind.fish }} _{{ cmd }}_widget
+{{/if}}
+"#;
+
+pub const NUSHELL: &str = r#"
+# # easychangedirectory
+# easychangedirectory --init nushell | save -f ~/.easychangedirectory.nu
+# source ~/.easychangedirectory.nu
+
+def --env --wrapped {{ cmd }} [...args: string] {
+  if ($args | is-empty) {
+    let cd_path = (^easychangedirectory --stdout)
+    if ($cd_path | is-not-empty) { cd $cd_path }
+  } else if ($args.0 =~ '^-+[a-zA-Z0-9]+') {
+    ^easychangedirectory ...$args
+  } else if ($args | length) == 1 and ($args.0 == '-' or ($args.0 | path type) == 'dir') {
+    cd $args.0
+  } else if ($args | length) == 1 {
+    let result = (^easychangedirectory query $args.0 | complete)
+    match $result.exit_code {
+      0 => { cd ($result.stdout | str trim) }
+      2 => {
+        let cd_path = (^easychangedirectory --stdout --history $args.0)
+        if ($cd_path | is-not-empty) { cd $cd_path }
+      }
+      _ => { error make --unspanned { msg: ($result.stderr | str trim) } }
+    }
+  } else {
+    print 'Too many arguments'
+  }
+}
+{{#if aliases}}
+
+def --env {{ cmd }}i [...query: string] {
+  let cd_path = (^easychangedirectory --stdout --history ($query | str join ' '))
+  if ($cd_path | is-not-empty) { cd $cd_path }
+}
+{{/if}}
 "#;
 
 pub const POWERSHELL: &str = r#"
 # # easychangedirectory
 # Invoke-Expression (& { (easychangedirectory --init powershell | Out-String) } )
 
-function ed {
-  if ($args.Length -eq 0) {
-    $temp_path = "{{ temp_path }}.$pid"
+# Windows has no /dev/tty to show the TUI on while stdout is read, so the destination is passed in a file
+# created by easychangedirectory itself, which is removed once it is read
+function {{ cmd }}_handoff($history) {
+  $temp_path = "{{ temp_path }}.$pid.$(Get-Random)"
+  if ($null -eq $history) {
     easychangedirectory -t $temp_path
-    $cd_path = (cat $temp_path)
+  } else {
+    easychangedirectory -t $temp_path --history $history
+  }
+  if (Test-Path $temp_path) {
+    $cd_path = (Get-Content -Raw $temp_path)
+    Remove-Item $temp_path
     cd $cd_path
-  } elseif ($args.Length -eq 1 -and $args[0] -match '^-+[a-zA-Z]+') {
-    easychangedirectory $args[0]
-  } elseif ($args.Length -eq 1) {
+  }
+}
+
+function {{ cmd }} {
+  if ($args.Length -eq 0) {
+    {{ cmd }}_handoff
+  } elseif ($args[0] -match '^-+[a-zA-Z0-9]+') {
+    easychangedirectory @args
+  } elseif ($args.Length -eq 1 -and ((Test-Path -PathType Container $args[0]) -or $args[0] -eq '-')) {
     cd $args[0]
+  } elseif ($args.Length -eq 1) {
+    $cd_path = (easychangedirectory query $args[0])
+    if ($LASTEXITCODE -eq 0) {
+      cd $cd_path
+    } elseif ($LASTEXITCODE -eq 2) {
+      {{ cmd }}_handoff $args[0]
+    }
   } else {
     echo 'Too many arguments'
   }
 }
+{{#if aliases}}
+
+function {{ cmd }}i {
+  {{ cmd }}_handoff "$args"
+}
+{{/if}}
+{{#if bind}}
+
+# `{{ bind.powershell }}` opens the TUI from the command line, `p` inserts the selected path at the cursor
+Set-PSReadLineKeyHandler -Chord '{{ bind.powershell }}' -ScriptBlock {
+  $temp_path = "{{ temp_path }}.$pid.$(Get-Random)"
+  easychangedirectory --widget -t $temp_path
+  if (Test-Path $temp_path) {
+    $out = (Get-Content -Raw $temp_path)
+    Remove-Item $temp_path
+    if ($out.StartsWith('cd ')) {
+      Set-Location -LiteralPath $out.Substring(3)
+    } elseif ($out.StartsWith('insert ')) {
+      [Microsoft.PowerShell.PSConsoleReadLine]::Insert("'" + $out.Substring(7).Replace("'", "''") + "'")
+    }
+  }
+  [Microsoft.PowerShell.PSConsoleReadLine]::InvokePrompt()
+}
+{{/if}}
+"#;
+
+pub const TCSH: &str = r#"
+# # easychangedirectory
+# easychangedirectory --init tcsh > ~/.easychangedirectory.tcsh
+# source ~/.easychangedirectory.tcsh
+
+# The globs of `=~` cannot repeat a pattern, so `expr` tells a flag by the same rule as the other shells
+
+alias {{ cmd }} 'set ed_args = (\!*)\
+set ed_path = ""\
+set ed_flag = 0\
+if ("$#ed_args" > 0) then\
+  set ed_flag = `expr "x$ed_args[1]" : "x--*[a-zA-Z0-9]"`\
+endif\
+if ("$#ed_args" == 0) then\
+  set ed_path = "`easychangedirectory --stdout`"\
+  if ("$ed_path" != "") cd "$ed_path"\
+else if ($ed_flag != 0) then\
+  easychangedirectory $ed_args:q\
+else if ("$#ed_args" == 1 && ("$ed_args[1]" == "-" || -d "$ed_args[1]")) then\
+  cd "$ed_args[1]"\
+else if ("$#ed_args" == 1) then\
+  set ed_path = "`easychangedirectory query $ed_args[1]:q`"; set ed_status = $status\
+  if ($ed_status == 0) then\
+    cd "$ed_path"\
+  else if ($ed_status == 2) then\
+    set ed_path = "`easychangedirectory --stdout --history $ed_args[1]:q`"\
+    if ("$ed_path" != "") cd "$ed_path"\
+  endif\
+else\
+  echo "Too many arguments"\
+endif'
+{{#if aliases}}
+
+alias {{ cmd }}i 'set ed_query = "\!*"\
+set ed_path = "`easychangedirectory --stdout --history $ed_query:q`"\
+if ("$ed_path" != "") cd "$ed_path"'
+{{/if}}
+"#;
+
+pub const XONSH: &str = r#"
+# # easychangedirectory
+# execx($(easychangedirectory --init xonsh), 'exec', __xonsh__.ctx, filename='easychangedirectory')
+
+import os
+import re
+
+from xonsh.tools import uncapturable, unthreadable
+
+
+# The TUI needs the terminal
+@unthreadable
+@uncapturable
+def _ed(args):
+    if len(args) == 0:
+        cd_path = $(easychangedirectory --stdout)
+        if cd_path:
+            cd @(cd_path)
+    elif re.match(r'^-+[a-zA-Z0-9]+', args[0]):
+        ![easychangedirectory @(args)]
+    elif len(args) == 1 and (args[0] == '-' or os.path.isdir(args[0])):
+        cd @(args[0])
+    elif len(args) == 1:
+        result = !(easychangedirectory query @(args[0]))
+        cd_path = result.output.strip()
+        if result.returncode == 0:
+            cd @(cd_path)
+        elif result.returncode == 2:
+            cd_path = $(easychangedirectory --stdout --history @(args[0]))
+            if cd_path:
+                cd @(cd_path)
+        else:
+            return 1
+    else:
+        print('Too many arguments')
+
+
+aliases['{{ cmd }}'] = _ed
+{{#if aliases}}
+
+
+@unthreadable
+@uncapturable
+def _edi(args):
+    cd_path = $(easychangedirectory --stdout --history @(' '.join(args)))
+    if cd_path:
+        cd @(cd_path)
+
+
+aliases['{{ cmd }}i'] = _edi
+{{/if}}
 "#;
 
 pub const ZSH: &str = r#"
 # # easychangedirectory
 # eval "$(easychangedirectory --init zsh)"
 
-function ed() {
+function {{ cmd }}() {
   if [[ "$#" -eq 0 ]]; then
-    temp_path="{{ temp_path }}.$$"
-    easychangedirectory -t "${temp_path}"
-    cd_path=`cat ${temp_path}`
-    cd "${cd_path}" || return
-  elif [[ "$#" -eq 1 ]] && [[ "$1" =~ ^-+[a-zA-Z]+ ]]; then
-    easychangedirectory "$1"
-  elif [[ "$#" -eq 1 ]]; then
+    cd_path="$(easychangedirectory --stdout)" && [[ -n "${cd_path}" ]] && cd "${cd_path}"
+  elif [[ "$1" =~ ^-+[a-zA-Z0-9]+ ]]; then
+    easychangedirectory "$@"
+  elif [[ "$#" -eq 1 ]] && { [[ -d "$1" ]] || [[ "$1" == "-" ]]; }; then
     cd "$1" || return
+  elif [[ "$#" -eq 1 ]]; then
+    cd_path="$(easychangedirectory query "$1")"
+    case "$?" in
+      0) cd "${cd_path}" || return ;;
+      2)
+        cd_path="$(easychangedirectory --stdout --history "$1")" && [[ -n "${cd_path}" ]] && cd "${cd_path}"
+        ;;
+      *) return 1 ;;
+    esac
   else
     echo 'Too many arguments'
   fi
 }
+{{#if aliases}}
+
+function {{ cmd }}i() {
+  cd_path="$(easychangedirectory --stdout --history "$*")" && [[ -n "${cd_path}" ]] && cd "${cd_path}"
+}
+{{/if}}
+{{#if bind}}
+
+# `{{ bind.zsh }}` opens the TUI from the command line, `p` inserts the selected path at the cursor
+function _{{ cmd }}_widget() {
+  local out precmd
+  out="$(easychangedirectory --widget --stdout < /dev/tty)"
+  case "${out}" in
+    cd\ *)
+      builtin cd -- "${out#cd }" || return
+      for precmd in $precmd_functions; do
+        $precmd
+      done
+      ;;
+    insert\ *) LBUFFER+="${(q)${out#insert }}" ;;
+  esac
+  zle reset-prompt
+}
+zle -N _{{ cmd }}_widget
+bindkey '{{ bind.zsh }}' _{{ cmd }}_widget
+{{/if}}
 "#;
 
 #[cfg(test)]
@@ -107,8 +439,12 @@ mod tests {
   #[test]
   fn test_shell_get_template() {
     assert_eq!(BASH, Shell::Bash.get_template());
+    assert_eq!(ELVISH, Shell::Elvish.get_template());
     assert_eq!(FISH, Shell::Fish.get_template());
+    assert_eq!(NUSHELL, Shell::Nushell.get_template());
     assert_eq!(POWERSHELL, Shell::Powershell.get_template());
+    assert_eq!(TCSH, Shell::Tcsh.get_template());
+    assert_eq!(XONSH, Shell::Xonsh.get_template());
     assert_eq!(ZSH, Shell::Zsh.get_template());
   }
 }
@@ -0,0 +1,55 @@
+//! Helpers shared by the tests
+
+use std::{
+  fs,
+  ops::Deref,
+  path::{Path, PathBuf},
+};
+
+/// A directory of its own in the temp directory, removed with its contents when dropped, even if the test fails
+#[derive(Debug)]
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+  /// `name` tells the tests apart, and the process id the test runs
+  pub fn new(name: &str) -> Self {
+    let path = std::env::temp_dir().join(format!("ed_test_{name}_{}", std::process::id()));
+    // Left by a run that was killed
+    let _ = fs::remove_dir_all(&path);
+    fs::create_dir_all(&path).unwrap();
+    TempDir(path)
+  }
+}
+
+impl Deref for TempDir {
+  type Target = Path;
+  fn deref(&self) -> &Path {
+    &self.0
+  }
+}
+
+impl AsRef<Path> for TempDir {
+  fn as_ref(&self) -> &Path {
+    &self.0
+  }
+}
+
+impl Drop for TempDir {
+  fn drop(&mut self) {
+    let _ = fs::remove_dir_all(&self.0);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_temp_dir() {
+    let dir = TempDir::new("temp_dir");
+    fs::write(dir.join("a"), "").unwrap();
+    let path = dir.to_path_buf();
+    drop(dir);
+    assert!(!path.exists());
+  }
+}